@@ -0,0 +1,289 @@
+//! Flappy.one — cashout authorization wire format.
+//!
+//! Single source of truth for the bytes a cashout signer signs and the
+//! `flappy_one` program verifies:
+//!   - V1 / V2 cashout authorization messages
+//!   - the auth hash preimage recorded (hashed) in
+//!     `Session.consumed_auth_ids`
+//!   - Ed25519 native-program instruction data, laid out exactly as
+//!     `verify_ed25519_signature` parses it
+//!
+//! `no_std` (+ `alloc`) so the program can link it; pubkeys are plain
+//! 32-byte arrays so off-chain signers need no Solana dependencies. The
+//! `signer` feature adds Ed25519 signing and the `hash` feature a software
+//! SHA-256 `auth_hash`, both for off-chain use.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+// ============================================================================
+// CONSTANTS
+// ============================================================================
+
+/// Domain separators prevent cross-protocol message reuse.
+/// Fixed 20 bytes — the first field of every cashout authorization message.
+///
+/// "FLAPPYONE_CASHOUT_V1" is retired with the original 108-byte V1 layout
+/// and never reused, so a signature over that layout cannot be parsed as
+/// the current one.
+pub const DOMAIN_SEPARATOR_V1: &[u8; 20] = b"FLAPPYONE_CASHOUT_1B";
+pub const DOMAIN_SEPARATOR_V2: &[u8; 20] = b"FLAPPYONE_CASHOUT_V2";
+
+/// Encoded message sizes.
+pub const MESSAGE_V1_LEN: usize = 141;
+pub const MESSAGE_V2_LEN: usize = 223;
+
+/// Size of the auth hash preimage.
+pub const AUTH_HASH_PREIMAGE_LEN: usize = 90;
+
+/// Ed25519 instruction data layout for a single embedded signature.
+///   [0]       number of signatures (1)
+///   [1]       padding
+///   [2..16)   Ed25519SignatureOffsets
+///   [16..48)  public key
+///   [48..112) signature
+///   [112..)   message
+pub const ED25519_PUBKEY_OFFSET: usize = 16;
+pub const ED25519_SIGNATURE_OFFSET: usize = 48;
+pub const ED25519_MESSAGE_OFFSET: usize = 112;
+
+/// Instruction index meaning "data lives in this instruction".
+pub const ED25519_CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+// ============================================================================
+// MESSAGES
+// ============================================================================
+
+/// Fields bound into a V1 cashout authorization message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CashoutMessageV1 {
+    pub player: [u8; 32],
+    pub session_index: u8,
+    /// Deposit mint; all zeroes = SOL.
+    pub mint: [u8; 32],
+    pub max_claimable: u64,
+    pub nonce: u64,
+    pub expiry: i64,
+    pub program_id: [u8; 32],
+}
+
+/// Fields bound into a V2 cashout authorization message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CashoutMessageV2 {
+    pub player: [u8; 32],
+    /// Session PDA.
+    pub session: [u8; 32],
+    pub session_index: u8,
+    pub deposit_tier: u8,
+    /// Deposit mint; all zeroes = SOL.
+    pub mint: [u8; 32],
+    pub amount: u64,
+    pub max_claimable: u64,
+    pub nonce: u64,
+    pub expiry: i64,
+    pub program_id: [u8; 32],
+    /// Genesis hash of the cluster.
+    pub cluster_id: [u8; 32],
+    /// Unix timestamp the authorization was signed.
+    pub issued_at: i64,
+    /// Cashout keeps the session active.
+    pub partial: bool,
+}
+
+impl CashoutMessageV1 {
+    /// Builds the canonical 141-byte V1 message.
+    ///
+    /// Layout (all fixed-width, no length ambiguity):
+    ///   [  0..20)  DOMAIN_SEPARATOR_V1   "FLAPPYONE_CASHOUT_1B"
+    ///   [ 20..52)  player pubkey         32 bytes
+    ///   [ 52..60)  max_claimable         u64 LE
+    ///   [ 60..68)  nonce                 u64 LE
+    ///   [ 68..76)  expiry                i64 LE
+    ///   [ 76..108) program_id            32 bytes
+    ///   [108]      session_index         u8
+    ///   [109..141) mint                  32 bytes (all zeroes = SOL)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(MESSAGE_V1_LEN);
+        msg.extend_from_slice(DOMAIN_SEPARATOR_V1); //  20
+        msg.extend_from_slice(&self.player); //  32
+        msg.extend_from_slice(&self.max_claimable.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.nonce.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.expiry.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.program_id); //  32
+        msg.push(self.session_index); //   1
+        msg.extend_from_slice(&self.mint); //  32
+        msg // 141
+    }
+}
+
+impl CashoutMessageV2 {
+    /// Builds the canonical 223-byte V2 message.
+    ///
+    /// Unlike V1 it binds the exact `amount`, the session PDA, the deposit
+    /// tier, and the cluster, so an authorization can only be redeemed for
+    /// one cashout of one session on one cluster. `issued_at` lets the
+    /// program reject authorizations signed before the session started,
+    /// and `partial` stops a closing cashout being redeemed as partial.
+    ///
+    /// Layout (all fixed-width, no length ambiguity):
+    ///   [  0..20)  DOMAIN_SEPARATOR_V2   "FLAPPYONE_CASHOUT_V2"
+    ///   [ 20..52)  player pubkey         32 bytes
+    ///   [ 52..84)  session PDA           32 bytes
+    ///   [ 84]      session_index         u8
+    ///   [ 85]      deposit_tier          u8
+    ///   [ 86..118) mint                  32 bytes (all zeroes = SOL)
+    ///   [118..126) amount                u64 LE
+    ///   [126..134) max_claimable         u64 LE
+    ///   [134..142) nonce                 u64 LE
+    ///   [142..150) expiry                i64 LE
+    ///   [150..182) program_id            32 bytes
+    ///   [182..214) cluster_id            32 bytes (genesis hash)
+    ///   [214..222) issued_at             i64 LE
+    ///   [222]      partial               u8 (0 | 1)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut msg = Vec::with_capacity(MESSAGE_V2_LEN);
+        msg.extend_from_slice(DOMAIN_SEPARATOR_V2); //  20
+        msg.extend_from_slice(&self.player); //  32
+        msg.extend_from_slice(&self.session); //  32
+        msg.push(self.session_index); //   1
+        msg.push(self.deposit_tier); //   1
+        msg.extend_from_slice(&self.mint); //  32
+        msg.extend_from_slice(&self.amount.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.max_claimable.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.nonce.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.expiry.to_le_bytes()); //   8
+        msg.extend_from_slice(&self.program_id); //  32
+        msg.extend_from_slice(&self.cluster_id); //  32
+        msg.extend_from_slice(&self.issued_at.to_le_bytes()); //   8
+        msg.push(self.partial as u8); //   1
+        msg // 223
+    }
+}
+
+// ============================================================================
+// AUTH HASH
+// ============================================================================
+
+/// Bytes whose SHA-256 is the auth hash recorded in
+/// `Session.consumed_auth_ids` to reject a second use of the same auth.
+///
+/// The program hashes this with the `sol_sha256` syscall; off-chain
+/// callers can use `auth_hash` (feature `hash`).
+///
+/// Layout (90 bytes):
+///   player (32) ‖ session_index (1) ‖ mint (32) ‖ max_claimable (8 LE)
+///   ‖ nonce (8 LE) ‖ expiry (8 LE) ‖ partial (1)
+pub fn auth_hash_preimage(
+    player: &[u8; 32],
+    session_index: u8,
+    mint: &[u8; 32],
+    max_claimable: u64,
+    nonce: u64,
+    expiry: i64,
+    partial: bool,
+) -> [u8; AUTH_HASH_PREIMAGE_LEN] {
+    let mut preimage = [0u8; AUTH_HASH_PREIMAGE_LEN];
+    preimage[0..32].copy_from_slice(player); // 32
+    preimage[32] = session_index; //  1
+    preimage[33..65].copy_from_slice(mint); // 32
+    preimage[65..73].copy_from_slice(&max_claimable.to_le_bytes()); //  8
+    preimage[73..81].copy_from_slice(&nonce.to_le_bytes()); //  8
+    preimage[81..89].copy_from_slice(&expiry.to_le_bytes()); //  8
+    preimage[89] = partial as u8; //  1
+    preimage
+}
+
+/// SHA-256 of `auth_hash_preimage` in software, for off-chain callers.
+#[cfg(feature = "hash")]
+pub fn auth_hash(
+    player: &[u8; 32],
+    session_index: u8,
+    mint: &[u8; 32],
+    max_claimable: u64,
+    nonce: u64,
+    expiry: i64,
+    partial: bool,
+) -> [u8; 32] {
+    use sha2::{Digest, Sha256};
+
+    Sha256::digest(auth_hash_preimage(
+        player,
+        session_index,
+        mint,
+        max_claimable,
+        nonce,
+        expiry,
+        partial,
+    ))
+    .into()
+}
+
+// ============================================================================
+// ED25519 INSTRUCTION DATA
+// ============================================================================
+
+/// The message does not fit the Ed25519 instruction's u16 length field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageTooLong {
+    pub len: usize,
+}
+
+impl fmt::Display for MessageTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "message of {} bytes exceeds the u16 length field", self.len)
+    }
+}
+
+/// Instruction data for the Ed25519 native program verifying one signature,
+/// with pubkey, signature and message all embedded (instruction indexes =
+/// 0xFFFF) — the only shape `verify_ed25519_signature` accepts.
+///
+/// Wrap it in an instruction for `Ed25519SigVerify111111111111111111111111111`
+/// with no accounts, placed before the cashout instruction.
+pub fn ed25519_instruction_data(
+    pubkey: &[u8; 32],
+    signature: &[u8; 64],
+    message: &[u8],
+) -> Result<Vec<u8>, MessageTooLong> {
+    let message_len =
+        u16::try_from(message.len()).map_err(|_| MessageTooLong { len: message.len() })?;
+
+    let mut data = Vec::with_capacity(ED25519_MESSAGE_OFFSET + message.len());
+    data.push(1); // number of signatures
+    data.push(0); // padding
+
+    // Ed25519SignatureOffsets
+    data.extend_from_slice(&(ED25519_SIGNATURE_OFFSET as u16).to_le_bytes());
+    data.extend_from_slice(&ED25519_CURRENT_INSTRUCTION.to_le_bytes());
+    data.extend_from_slice(&(ED25519_PUBKEY_OFFSET as u16).to_le_bytes());
+    data.extend_from_slice(&ED25519_CURRENT_INSTRUCTION.to_le_bytes());
+    data.extend_from_slice(&(ED25519_MESSAGE_OFFSET as u16).to_le_bytes());
+    data.extend_from_slice(&message_len.to_le_bytes());
+    data.extend_from_slice(&ED25519_CURRENT_INSTRUCTION.to_le_bytes());
+
+    data.extend_from_slice(pubkey); // [16..48)
+    data.extend_from_slice(signature); // [48..112)
+    data.extend_from_slice(message); // [112..)
+    Ok(data)
+}
+
+/// Signs `message` with `signing_key` and returns the matching Ed25519
+/// instruction data.
+#[cfg(feature = "signer")]
+pub fn sign_ed25519_instruction_data(
+    signing_key: &ed25519_dalek::SigningKey,
+    message: &[u8],
+) -> Result<Vec<u8>, MessageTooLong> {
+    use ed25519_dalek::Signer;
+
+    let signature = signing_key.sign(message);
+    ed25519_instruction_data(
+        signing_key.verifying_key().as_bytes(),
+        &signature.to_bytes(),
+        message,
+    )
+}
@@ -0,0 +1,156 @@
+//! Golden vectors for the cashout authorization wire format.
+//!
+//! These bytes are what the `flappy_one` program verifies on-chain. Any
+//! signer (e.g. the `authorize-cashout` edge function) must reproduce them
+//! exactly; a change here is a breaking protocol change. The message
+//! vectors are mirrored in supabase/functions/authorize-cashout/message_test.ts.
+
+use ed25519_dalek::{Signer, SigningKey, Verifier};
+use flappy_one_auth::*;
+use sha2::{Digest, Sha256};
+
+/// `8b4U8WX2SNJ1p53m2w6GcMjCooo7KTGdWZiFBmcZ4MwK` (devnet program id).
+const PROGRAM_ID: [u8; 32] = [
+    0x70, 0xbb, 0x7a, 0x1b, 0xff, 0xff, 0xba, 0x4a, 0x46, 0x76, 0xd5, 0x9e, 0xb9, 0xcb, 0xd1, 0x2b,
+    0xe5, 0x8e, 0xeb, 0x92, 0xcf, 0xb1, 0x13, 0x78, 0xd1, 0xc1, 0xf1, 0x30, 0x79, 0x99, 0xb4, 0x76,
+];
+
+const V1_HEX: &str = concat!(
+    "464c415050594f4e455f434153484f55545f3142", // "FLAPPYONE_CASHOUT_1B"
+    "1111111111111111111111111111111111111111111111111111111111111111", // player
+    "002f685900000000", // max_claimable = 1_500_000_000
+    "0700000000000000", // nonce = 7
+    "78f1536500000000", // expiry = 1_700_000_120
+    "70bb7a1bffffba4a4676d59eb9cbd12be58eeb92cfb11378d1c1f1307999b476", // program_id
+    "02", // session_index
+    "0000000000000000000000000000000000000000000000000000000000000000", // mint (SOL)
+);
+
+const V2_HEX: &str = concat!(
+    "464c415050594f4e455f434153484f55545f5632", // "FLAPPYONE_CASHOUT_V2"
+    "1111111111111111111111111111111111111111111111111111111111111111", // player
+    "3333333333333333333333333333333333333333333333333333333333333333", // session PDA
+    "02", // session_index
+    "05", // deposit_tier
+    "4444444444444444444444444444444444444444444444444444444444444444", // mint
+    "801a060000000000", // amount = 400_000
+    "40420f0000000000", // max_claimable = 1_000_000
+    "0700000000000000", // nonce = 7
+    "78f1536500000000", // expiry = 1_700_000_120
+    "70bb7a1bffffba4a4676d59eb9cbd12be58eeb92cfb11378d1c1f1307999b476", // program_id
+    "5555555555555555555555555555555555555555555555555555555555555555", // cluster_id
+    "00f1536500000000", // issued_at = 1_700_000_000
+    "01", // partial
+);
+
+const AUTH_HASH_HEX: &str = "edf772e4b8e731d7beea94276df6062b4f4a9484b2318ab63effa256245da17a";
+
+/// Signer secret key = [0x42; 32].
+const SIGNER_PUBKEY_HEX: &str = "2152f8d19b791d24453242e15f2eab6cb7cffa7b6a5ed30097960e069881db12";
+
+/// Ed25519 instruction header for the V1 vector: 1 signature, offsets
+/// (sig 48, pk 16, msg 112 / len 141), all indexes 0xFFFF.
+const ED25519_HEADER_HEX: &str = "01003000ffff1000ffff70008d00ffff";
+
+const V1_SIGNATURE_HEX: &str = concat!(
+    "68ce1a00e70e5f988b8a9972957db1e082171ec37d61d92cca2fada070c9f497",
+    "1203253af5fed332aba76e518e39cc76b8314b37e0348c863d5702450b7c4205",
+);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn v1_vector() -> CashoutMessageV1 {
+    CashoutMessageV1 {
+        player: [0x11; 32],
+        session_index: 2,
+        mint: [0; 32],
+        max_claimable: 1_500_000_000,
+        nonce: 7,
+        expiry: 1_700_000_120,
+        program_id: PROGRAM_ID,
+    }
+}
+
+fn signer() -> SigningKey {
+    SigningKey::from_bytes(&[0x42; 32])
+}
+
+#[test]
+fn v1_message_matches_golden_vector() {
+    let msg = v1_vector().to_bytes();
+    assert_eq!(msg.len(), MESSAGE_V1_LEN);
+    assert_eq!(hex(&msg), V1_HEX);
+}
+
+#[test]
+fn v2_message_matches_golden_vector() {
+    let msg = CashoutMessageV2 {
+        player: [0x11; 32],
+        session: [0x33; 32],
+        session_index: 2,
+        deposit_tier: 5,
+        mint: [0x44; 32],
+        amount: 400_000,
+        max_claimable: 1_000_000,
+        nonce: 7,
+        expiry: 1_700_000_120,
+        program_id: PROGRAM_ID,
+        cluster_id: [0x55; 32],
+        issued_at: 1_700_000_000,
+        partial: true,
+    }
+    .to_bytes();
+    assert_eq!(msg.len(), MESSAGE_V2_LEN);
+    assert_eq!(hex(&msg), V2_HEX);
+}
+
+#[test]
+fn auth_hash_matches_golden_vector() {
+    let v = v1_vector();
+    let preimage = auth_hash_preimage(
+        &v.player,
+        v.session_index,
+        &v.mint,
+        v.max_claimable,
+        v.nonce,
+        v.expiry,
+        false,
+    );
+    assert_eq!(hex(&Sha256::digest(preimage)), AUTH_HASH_HEX);
+}
+
+#[test]
+fn ed25519_instruction_matches_golden_vector() {
+    let signing_key = signer();
+    let verifying_key = signing_key.verifying_key();
+    let msg = v1_vector().to_bytes();
+    let signature = signing_key.sign(&msg);
+    assert_eq!(hex(verifying_key.as_bytes()), SIGNER_PUBKEY_HEX);
+    assert_eq!(hex(&signature.to_bytes()), V1_SIGNATURE_HEX);
+
+    let data =
+        ed25519_instruction_data(verifying_key.as_bytes(), &signature.to_bytes(), &msg).unwrap();
+    assert_eq!(data.len(), ED25519_MESSAGE_OFFSET + MESSAGE_V1_LEN);
+    assert_eq!(hex(&data[..ED25519_PUBKEY_OFFSET]), ED25519_HEADER_HEX);
+    assert_eq!(
+        hex(&data[ED25519_PUBKEY_OFFSET..ED25519_SIGNATURE_OFFSET]),
+        SIGNER_PUBKEY_HEX
+    );
+    assert_eq!(
+        hex(&data[ED25519_SIGNATURE_OFFSET..ED25519_MESSAGE_OFFSET]),
+        V1_SIGNATURE_HEX
+    );
+    assert_eq!(&data[ED25519_MESSAGE_OFFSET..], msg.as_slice());
+
+    // The embedded signature really verifies over the embedded message.
+    verifying_key.verify(&msg, &signature).unwrap();
+}
+
+#[test]
+fn ed25519_instruction_rejects_oversized_message() {
+    let msg = vec![0u8; u16::MAX as usize + 1];
+    let err = ed25519_instruction_data(&[0; 32], &[0; 64], &msg).unwrap_err();
+    assert_eq!(err, MessageTooLong { len: msg.len() });
+}
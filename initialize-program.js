@@ -10,6 +10,8 @@
  * Reads from environment or defaults:
  *   FLAPPY_PROGRAM_ID   — deployed program ID
  *   TREASURY_PUBKEY      — wallet that receives 10% fees
+ *   PRICE_FEED_PUBKEY    — Pyth SOL/USD price account (prices USD tiers)
+ *   ORACLE_PROGRAM_ID    — program that owns the price account
  *   AUTHORITY_KEYPAIR    — path to authority keypair JSON
  *   DEPLOYER_KEYPAIR     — path to deployer keypair JSON (pays for tx)
  */
@@ -53,6 +55,19 @@ const TREASURY_PUBKEY = new PublicKey(
     "BdjgaSf75uTDSD1CdR9vDmKw6KA9xmAPdqRiGeKp8Y3S"
 );
 
+// Pyth SOL/USD price feed account (PriceUpdateV2, shard 0 of the push
+// oracle) and the Pyth receiver program that owns it. A legacy Pyth v2
+// price account and its oracle program are still accepted via env.
+const PRICE_FEED_PUBKEY = new PublicKey(
+  process.env.PRICE_FEED_PUBKEY ||
+    "7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE"
+);
+
+const ORACLE_PROGRAM_ID = new PublicKey(
+  process.env.ORACLE_PROGRAM_ID ||
+    "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ"
+);
+
 // ── Load keypairs ──────────────────────────────────────────────────────────
 
 function loadKeypair(envVar, defaultPath) {
@@ -81,6 +96,11 @@ const [configPDA, configBump] = PublicKey.findProgramAddressSync(
   PROGRAM_ID
 );
 
+const [tierConfigPDA] = PublicKey.findProgramAddressSync(
+  [Buffer.from("tiers")],
+  PROGRAM_ID
+);
+
 const [vaultPDA, vaultBump] = PublicKey.findProgramAddressSync(
   [Buffer.from("vault")],
   PROGRAM_ID
@@ -107,6 +127,8 @@ async function main() {
   console.log("=== Initializing Flappy.one Program ===");
   console.log("  Program ID:  ", PROGRAM_ID.toBase58());
   console.log("  Treasury:    ", TREASURY_PUBKEY.toBase58());
+  console.log("  Price feed:  ", PRICE_FEED_PUBKEY.toBase58());
+  console.log("  Oracle:      ", ORACLE_PROGRAM_ID.toBase58());
   console.log("  Authority:   ", authorityKeypair.publicKey.toBase58());
   console.log("  Config PDA:  ", configPDA.toBase58());
   console.log("  Vault PDA:   ", vaultPDA.toBase58());
@@ -114,10 +136,13 @@ async function main() {
   console.log("");
 
   // Data: [8-byte discriminator][32-byte treasury pubkey]
+  //       [32-byte price feed pubkey][32-byte oracle program id]
   const disc = anchorDiscriminator("initialize");
-  const data = Buffer.alloc(8 + 32);
+  const data = Buffer.alloc(8 + 32 + 32 + 32);
   disc.copy(data, 0);
   TREASURY_PUBKEY.toBuffer().copy(data, 8);
+  PRICE_FEED_PUBKEY.toBuffer().copy(data, 40);
+  ORACLE_PROGRAM_ID.toBuffer().copy(data, 72);
 
   const ix = new TransactionInstruction({
     programId: PROGRAM_ID,
@@ -136,6 +161,8 @@ async function main() {
       },
       // config PDA — writable (being created)
       { pubkey: configPDA, isSigner: false, isWritable: true },
+      // tier config PDA — writable (being created)
+      { pubkey: tierConfigPDA, isSigner: false, isWritable: true },
       // vault PDA — writable (Anchor records bump)
       { pubkey: vaultPDA, isSigner: false, isWritable: true },
       // system program
@@ -1,8 +1,16 @@
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
-use solana_program::ed25519_program;
-use solana_program::hash;
-use solana_program::sysvar::instructions as ix_sysvar;
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_2022::spl_token_2022;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+};
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface};
+use flappy_one_auth::{CashoutMessageV1, CashoutMessageV2};
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hash;
+use anchor_lang::solana_program::sysvar::instructions as ix_sysvar;
 
 // ============================================================================
 // PROGRAM ID — Replace after `anchor keys list` or `anchor build`
@@ -13,20 +21,113 @@ declare_id!("8b4U8WX2SNJ1p53m2w6GcMjCooo7KTGdWZiFBmcZ4MwK");
 // CONSTANTS
 // ============================================================================
 
-/// Domain separator prevents cross-protocol message reuse.
-/// Fixed 20 bytes — included in every cashout authorization message.
-const DOMAIN_SEPARATOR: &[u8; 20] = b"FLAPPYONE_CASHOUT_V1";
-
-/// 10% platform fee = 1000 basis points.
-const FEE_BPS: u64 = 1_000;
+/// Platform fee set at initialize (10% = 1000 basis points).
+/// The admin can change it later via `set_fee_bps`.
+const DEFAULT_FEE_BPS: u16 = 1_000;
+/// Hard cap on the configurable fee (20%). Baked into the program so a
+/// compromised admin key cannot raise the rake beyond it.
+const MAX_FEE_BPS: u16 = 2_000;
 const BPS_DENOMINATOR: u64 = 10_000;
 
-/// Allowed deposit tiers in lamports.
-const TIER_1_LAMPORTS: u64 = 1_000_000_000; // 1 SOL
-const TIER_5_LAMPORTS: u64 = 5_000_000_000; // 5 SOL
-const TIER_20_LAMPORTS: u64 = 20_000_000_000; // 20 SOL
+/// Share of each cashout fee paid to the player's referrer, in basis points
+/// of the fee (20% of the fee). The admin can change it via
+/// `set_referral_share`, up to the whole fee.
+const DEFAULT_REFERRAL_SHARE_BPS: u16 = 2_000;
+
+/// `VaultConfig.fee_mode` values — what the cashout fee is charged on.
+/// Gross: the whole cashout amount. Profit: only the part of the session's
+/// cumulative claims above its deposit.
+const FEE_MODE_GROSS: u8 = 0;
+const FEE_MODE_PROFIT: u8 = 1;
+
+/// Deposit tiers seeded into TierConfig at initialize (id, USD cents).
+/// Priced in SOL at deposit time through the configured price feed.
+const TIER_1_USD_CENTS: u64 = 100; // $1
+const TIER_5_USD_CENTS: u64 = 500; // $5
+const TIER_20_USD_CENTS: u64 = 2_000; // $20
+const DEFAULT_TIERS: [(u8, u64); 3] = [
+    (1, TIER_1_USD_CENTS),
+    (5, TIER_5_USD_CENTS),
+    (20, TIER_20_USD_CENTS),
+];
+
+/// Default maximum age of the SOL/USD price used to price USD tiers.
+const DEFAULT_MAX_PRICE_AGE_SECS: i64 = 60;
+
+/// Widest confidence interval accepted, relative to the price (2%).
+const MAX_PRICE_CONF_BPS: u64 = 200;
+
+/// Pyth pull-oracle `PriceUpdateV2` account (owned by the Pyth receiver
+/// program): Anchor discriminator and the fully verified layout's length.
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [0x22, 0xf1, 0x23, 0x63, 0x9d, 0x7e, 0xf4, 0xcd];
+const PRICE_UPDATE_V2_VERIFICATION_FULL: u8 = 1;
+const PRICE_UPDATE_V2_MIN_LEN: usize = 133;
+
+/// Legacy Pyth v2 price account layout — only the fields
+/// `load_sol_usd_price` reads.
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+const PYTH_VERSION_2: u32 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+const PYTH_PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+/// Longest the outgoing authority may stay valid after
+/// `accept_config_change`, so authorizations issued just before a rotation
+/// can still be redeemed. Each proposal picks its own window up to this;
+/// 0 revokes the outgoing key immediately (e.g. after a leak).
+const MAX_AUTHORITY_OVERLAP_SECS: i64 = 600; // 10 minutes
+
+/// Default / minimum time without a server checkpoint before a player may
+/// reclaim their deposit from an active session via `reclaim_stale_session`.
+const DEFAULT_STALE_SESSION_TIMEOUT_SECS: i64 = 86_400; // 24 hours
+const MIN_STALE_SESSION_TIMEOUT_SECS: i64 = 3_600; // 1 hour
+
+/// Maximum size of the cashout signer set (M-of-N, N ≤ MAX_SIGNERS).
+const MAX_SIGNERS: usize = 5;
+
+/// Maximum number of entries in the TierConfig registry.
+const MAX_TIERS: usize = 16;
+
+/// Default furthest-ahead expiry a cashout authorization may carry.
+const DEFAULT_MAX_AUTH_TTL_SECS: i64 = 300; // 5 minutes
+
+/// Clock skew tolerated between the signer's wall clock (V2 `issued_at`)
+/// and the cluster clock, which can lag or lead it by several seconds.
+const ISSUED_AT_SKEW_SECS: i64 = 60;
+
+/// Consumed authorization ids remembered per session (ring buffer).
+/// Older authorizations are already dead through the nonce check.
+const AUTH_RING_LEN: usize = 8;
+
+/// Maximum number of SPL mints accepted for token deposits.
+const MAX_ALLOWED_MINTS: usize = 4;
+
+/// Maximum number of recipients in the cashout fee-split table.
+const MAX_FEE_SPLITS: usize = 4;
+
+/// Size of the original release's VaultConfig (after the discriminator),
+/// the only layout `migrate_config` accepts.
+const LEGACY_CONFIG_LEN: usize = 66;
+
+/// `VaultConfig.paused` bits — each flow can be halted independently.
+const PAUSE_DEPOSITS: u8 = 1 << 0;
+const PAUSE_CASHOUTS: u8 = 1 << 1;
+const PAUSE_FORCE_CLOSES: u8 = 1 << 2;
+const PAUSE_ALL: u8 = PAUSE_DEPOSITS | PAUSE_CASHOUTS | PAUSE_FORCE_CLOSES;
+
+/// `VaultConfig.message_versions` bits — cashout message layouts accepted.
+const MESSAGE_V1: u8 = 1 << 0;
+const MESSAGE_V2: u8 = 1 << 1;
+const MESSAGE_ALL: u8 = MESSAGE_V1 | MESSAGE_V2;
+
+/// `force_close_on_death` cause codes (mirrors the game server's `die()`).
+const DEATH_CAUSE_UNKNOWN: u8 = 0;
+const DEATH_CAUSE_BULLET: u8 = 1;
+const DEATH_CAUSE_PIPE: u8 = 2;
+const DEATH_CAUSE_BORDER: u8 = 3;
 
 /// Session status values (u8 for safe zero-default on fresh accounts).
+#[allow(dead_code)] // never written; the zero a fresh account starts with
 const STATUS_INACTIVE: u8 = 0;
 const STATUS_ACTIVE: u8 = 1;
 const STATUS_CLOSED: u8 = 2;
@@ -44,551 +145,4060 @@ pub mod flappy_one {
     // ────────────────────────────────────────────────────────────────────────
 
     /// Creates the global VaultConfig PDA and records the vault PDA bump.
+    /// Also creates the TierConfig registry seeded with the $1 / $5 / $20
+    /// tiers, priced through `price_feed`.
     ///
-    /// Must be called exactly once after deployment.
+    /// Must be called exactly once after deployment. A deployment whose
+    /// config was created by the original release runs `migrate_config`
+    /// instead.
+    ///
+    /// The payer becomes the config admin, which can later rotate the
+    /// authority and treasury via `propose_config_change` and hand itself
+    /// off via `propose_admin`.
     ///
     /// # Arguments
-    /// * `treasury` — Pubkey that receives 10 % fees on cashouts.
-    pub fn initialize(ctx: Context<Initialize>, treasury: Pubkey) -> Result<()> {
-        let config = &mut ctx.accounts.config;
-        config.treasury = treasury;
-        config.authority = ctx.accounts.authority.key();
-        config.vault_bump = ctx.bumps.vault;
-        config.config_bump = ctx.bumps.config;
+    /// * `treasury`       — Pubkey that receives the platform fee on cashouts.
+    /// * `price_feed`     — Pyth SOL/USD price account used for USD tiers.
+    /// * `oracle_program` — Program that must own `price_feed`.
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        treasury: Pubkey,
+        price_feed: Pubkey,
+        oracle_program: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.config.set_inner(initial_config(
+            treasury,
+            ctx.accounts.authority.key(),
+            ctx.accounts.payer.key(),
+            ctx.bumps.vault,
+            ctx.bumps.config,
+            price_feed,
+            oracle_program,
+        ));
+        ctx.accounts.tier_config.set_inner(TierConfig {
+            tiers: default_tiers(),
+            bump: ctx.bumps.tier_config,
+        });
+
+        // `require_vault_reserve` keeps the rent-exempt minimum in the
+        // vault, so it must be there from the start or the first cashouts
+        // would eat into player deposits and fail.
+        fund_vault_rent(
+            &ctx.accounts.payer,
+            &ctx.accounts.vault,
+            &ctx.accounts.system_program,
+        )?;
 
+        let config = &ctx.accounts.config;
         emit!(ConfigInitialized {
             treasury,
             authority: config.authority,
+            admin: config.admin,
+            fee_bps: config.fee_bps,
         });
         Ok(())
     }
 
     // ────────────────────────────────────────────────────────────────────────
-    // deposit — player enters a game session
+    // migrate_config — upgrade authority converts the pre-upgrade config
     // ────────────────────────────────────────────────────────────────────────
 
-    /// Transfers SOL from player → PDA-controlled vault and activates a session.
+    /// Converts a config PDA written by the original release (66-byte
+    /// layout: treasury, authority, vault and config bumps) in place:
+    /// reallocates it to the current layout, keeps the treasury, authority
+    /// and bumps, and fills every other field as `initialize` does. Also
+    /// creates the TierConfig registry and funds the vault to rent
+    /// exemption if needed.
+    ///
+    /// The upgrade authority becomes the config admin, since the old layout
+    /// had none.
+    ///
+    /// Only the config is migrated. Session PDAs are now seeded by index
+    /// and PlayerProfile accounts did not exist, so sessions opened before
+    /// the upgrade cannot be settled afterwards: settle every open session
+    /// before deploying, or deploy under a fresh program id instead.
+    ///
+    /// # Arguments
+    /// * `price_feed`     — Pyth SOL/USD price account used for USD tiers.
+    /// * `oracle_program` — Program that must own `price_feed`.
     ///
     /// # Guards
-    /// - `tier` must be 1, 5, or 20.
-    /// - Session must NOT already be active (no double-deposit).
-    /// - SOL goes to a PDA; no private key can move it.
-    pub fn deposit(ctx: Context<Deposit>, tier: u8) -> Result<()> {
-        // GUARD: tier ∈ {1, 5, 20}
-        let deposit_lamports = match tier {
-            1 => TIER_1_LAMPORTS,
-            5 => TIER_5_LAMPORTS,
-            20 => TIER_20_LAMPORTS,
-            _ => return Err(FlappyError::InvalidTier.into()),
-        };
-
-        let session = &mut ctx.accounts.session;
+    /// - Signer must be the program's upgrade authority.
+    /// - Config is exactly the pre-upgrade layout (not yet migrated).
+    pub fn migrate_config(
+        ctx: Context<MigrateConfig>,
+        price_feed: Pubkey,
+        oracle_program: Pubkey,
+    ) -> Result<()> {
+        let config_info = ctx.accounts.config.to_account_info();
+        let legacy = parse_legacy_config(&config_info.try_borrow_data()?)?;
+        let config = initial_config(
+            legacy.treasury,
+            legacy.authority,
+            ctx.accounts.upgrade_authority.key(),
+            legacy.vault_bump,
+            legacy.config_bump,
+            price_feed,
+            oracle_program,
+        );
 
-        // GUARD: prevent double-deposit while a session is live.
-        // On a brand-new account (init_if_needed just created it) player == default.
-        // On a recycled account status must be Closed (not Active).
-        if session.player != Pubkey::default() {
-            require!(
-                session.status != STATUS_ACTIVE,
-                FlappyError::SessionAlreadyActive
-            );
+        // ── Grow the account, topping up rent from the upgrade authority ──
+        let new_len = 8 + VaultConfig::INIT_SPACE;
+        let top_up = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(config_info.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.upgrade_authority.to_account_info(),
+                        to: config_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
         }
+        config_info.realloc(new_len, true)?;
+        config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
 
-        // ── CPI: player → vault (player is signer, no invoke_signed) ──
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.player.to_account_info(),
-                    to: ctx.accounts.vault.to_account_info(),
-                },
-            ),
-            deposit_lamports,
+        ctx.accounts.tier_config.set_inner(TierConfig {
+            tiers: default_tiers(),
+            bump: ctx.bumps.tier_config,
+        });
+        fund_vault_rent(
+            &ctx.accounts.upgrade_authority,
+            &ctx.accounts.vault,
+            &ctx.accounts.system_program,
         )?;
 
-        // ── Write session state ──
-        session.player = ctx.accounts.player.key();
-        session.deposit_tier = tier;
-        session.deposit_amount = deposit_lamports;
-        session.status = STATUS_ACTIVE;
-        session.max_claimable = 0; // server sets via cashout auth
-        session.started_at = Clock::get()?.unix_timestamp;
-        // Increment nonce to invalidate any stale authorizations
-        session.nonce = session.nonce.checked_add(1).unwrap_or(1);
-        session.last_auth_hash = [0u8; 32];
-        session.auth_expiry = 0;
-        session.bump = ctx.bumps.session;
-
-        emit!(SessionCreated {
-            player: session.player,
-            tier,
-            deposit_lamports,
-            nonce: session.nonce,
+        emit!(ConfigMigrated {
+            treasury: config.treasury,
+            authority: config.authority,
+            admin: config.admin,
         });
         Ok(())
     }
 
     // ────────────────────────────────────────────────────────────────────────
-    // cashout — server-authorized payout
+    // propose_admin — admin stages its successor
     // ────────────────────────────────────────────────────────────────────────
 
-    /// Pays out earnings to the player (minus 10 % fee to treasury).
+    /// Stages a new config admin. Nothing changes until the incoming admin
+    /// signs `accept_admin`. Proposing again overwrites the pending admin;
+    /// proposing the default pubkey cancels it.
     ///
-    /// The transaction **must** include an Ed25519 program instruction
-    /// (at any index before this one) that verifies the server authority's
-    /// signature over the canonical cashout message.
+    /// # Guards
+    /// - Signer must be the stored admin.
+    pub fn propose_admin(ctx: Context<AdminConfig>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.config.pending_admin = new_admin;
+
+        emit!(AdminChangeProposed {
+            admin: ctx.accounts.admin.key(),
+            pending_admin: new_admin,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // accept_admin — incoming admin completes the handoff
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Makes the pending admin the config admin. Must be signed by the
+    /// pending admin, so the key is proven usable before the old one loses
+    /// control.
     ///
-    /// # Arguments
-    /// * `amount`        — lamports the player wants (≤ max_claimable).
-    /// * `max_claimable` — server-authorized ceiling (signed in the auth).
-    /// * `nonce`         — must match session.nonce.
-    /// * `expiry`        — unix timestamp; tx rejected after this.
+    /// # Guards
+    /// - A change is pending.
+    /// - Signer must be the pending admin.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let old_admin = config.admin;
+        config.admin = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+
+        emit!(AdminUpdated {
+            old_admin,
+            new_admin: config.admin,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_fee_bps — admin adjusts the platform fee
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets the platform fee charged on cashouts, in basis points.
     ///
-    /// # Guards (in order)
-    /// 1. Session active
-    /// 2. Signer == session.player
-    /// 3. Nonce match (anti-replay)
-    /// 4. Expiry not passed
-    /// 5. amount ≤ max_claimable
-    /// 6. amount > 0
-    /// 7. Ed25519 signature verified (authority + message content)
-    /// 8. Auth hash unique (double-spend prevention)
-    /// 9. State updated BEFORE transfers (checks-effects-interactions)
-    pub fn cashout(
-        ctx: Context<Cashout>,
-        amount: u64,
-        max_claimable: u64,
-        nonce: u64,
-        expiry: i64,
-    ) -> Result<()> {
-        let session = &mut ctx.accounts.session;
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `fee_bps` ≤ MAX_FEE_BPS.
+    pub fn set_fee_bps(ctx: Context<AdminConfig>, fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= MAX_FEE_BPS, FlappyError::FeeTooHigh);
 
-        // 1. Session must be active
-        require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+        let config = &mut ctx.accounts.config;
+        let old_fee_bps = config.fee_bps;
+        config.fee_bps = fee_bps;
+
+        emit!(FeeUpdated {
+            old_fee_bps,
+            new_fee_bps: fee_bps,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_fee_mode — admin picks gross or profit-only fees
+    // ────────────────────────────────────────────────────────────────────────
 
-        // 2. Signer must own the session
+    /// Sets whether the cashout fee is charged on the whole amount
+    /// (FEE_MODE_GROSS) or only on profit above the deposit
+    /// (FEE_MODE_PROFIT).
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `fee_mode` is a known mode.
+    pub fn set_fee_mode(ctx: Context<AdminConfig>, fee_mode: u8) -> Result<()> {
         require!(
-            session.player == ctx.accounts.player.key(),
-            FlappyError::UnauthorizedPlayer
+            fee_mode == FEE_MODE_GROSS || fee_mode == FEE_MODE_PROFIT,
+            FlappyError::InvalidFeeMode
         );
 
-        // 3. Nonce must match — prevents replaying old authorizations
-        require!(nonce == session.nonce, FlappyError::InvalidNonce);
+        let config = &mut ctx.accounts.config;
+        let old_fee_mode = config.fee_mode;
+        config.fee_mode = fee_mode;
 
-        // 4. Authorization must not be expired
-        let clock = Clock::get()?;
-        require!(clock.unix_timestamp < expiry, FlappyError::AuthorizationExpired);
+        emit!(FeeModeUpdated {
+            old_fee_mode,
+            new_fee_mode: fee_mode,
+        });
+        Ok(())
+    }
 
-        // 5. Amount within authorized ceiling
-        require!(amount <= max_claimable, FlappyError::AmountExceedsAuthorized);
+    // ────────────────────────────────────────────────────────────────────────
+    // propose_config_change — admin stages a new authority + treasury
+    // ────────────────────────────────────────────────────────────────────────
 
-        // 6. No zero-amount cashouts
-        require!(amount > 0, FlappyError::ZeroCashout);
+    /// Stages a new authority and treasury. Nothing changes until the
+    /// incoming authority signs `accept_config_change`.
+    ///
+    /// Proposing again overwrites any pending change.
+    ///
+    /// # Arguments
+    /// * `new_authority` — incoming game server signing key.
+    /// * `new_treasury`  — incoming fee recipient (may equal the current one).
+    /// * `overlap_secs`  — how long the outgoing authority stays valid after
+    ///   the handoff; 0 revokes it immediately.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - Neither key may be the default pubkey.
+    /// - 0 ≤ `overlap_secs` ≤ MAX_AUTHORITY_OVERLAP_SECS.
+    pub fn propose_config_change(
+        ctx: Context<AdminConfig>,
+        new_authority: Pubkey,
+        new_treasury: Pubkey,
+        overlap_secs: i64,
+    ) -> Result<()> {
+        require!(
+            new_authority != Pubkey::default() && new_treasury != Pubkey::default(),
+            FlappyError::InvalidConfigChange
+        );
+        require!(
+            (0..=MAX_AUTHORITY_OVERLAP_SECS).contains(&overlap_secs),
+            FlappyError::InvalidConfigChange
+        );
 
-        // 7. Verify Ed25519 signature via instructions sysvar
-        verify_ed25519_signature(
-            &ctx.accounts.instructions_sysvar,
-            &ctx.accounts.config.authority,
-            &ctx.accounts.player.key(),
-            max_claimable,
-            nonce,
-            expiry,
-            &crate::id(),
-        )?;
+        let config = &mut ctx.accounts.config;
+        config.pending_authority = new_authority;
+        config.pending_treasury = new_treasury;
+        config.pending_overlap_secs = overlap_secs;
 
-        // 8. Replay check — auth hash must differ from last used
-        let auth_hash = compute_auth_hash(
-            &ctx.accounts.player.key(),
-            max_claimable,
-            nonce,
-            expiry,
-        );
-        require!(session.last_auth_hash != auth_hash, FlappyError::ReplayDetected);
+        emit!(ConfigChangeProposed {
+            admin: ctx.accounts.admin.key(),
+            pending_authority: new_authority,
+            pending_treasury: new_treasury,
+            overlap_secs,
+        });
+        Ok(())
+    }
 
-        // ── EFFECTS — update state before any transfers ──
-        session.status = STATUS_CLOSED;
-        session.max_claimable = max_claimable;
-        session.last_auth_hash = auth_hash;
-        session.auth_expiry = expiry;
-        session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+    // ────────────────────────────────────────────────────────────────────────
+    // accept_config_change — incoming authority completes the handoff
+    // ────────────────────────────────────────────────────────────────────────
 
-        // ── FEE MATH ──
-        let fee = amount
-            .checked_mul(FEE_BPS)
-            .ok_or(FlappyError::MathOverflow)?
-            .checked_div(BPS_DENOMINATOR)
-            .ok_or(FlappyError::MathOverflow)?;
-        let player_payout = amount
-            .checked_sub(fee)
-            .ok_or(FlappyError::MathOverflow)?;
+    /// Applies the staged authority + treasury. Must be signed by the
+    /// pending authority, proving the new server key is live before any
+    /// cashout depends on it.
+    ///
+    /// The outgoing authority always leaves the cashout signer set: its
+    /// slot goes to the incoming key, or is dropped if the incoming key is
+    /// already a member. The outgoing key remains accepted for the proposal's
+    /// `overlap_secs` so in-flight authorizations are not stranded; with 0
+    /// it (and any earlier previous authority) is revoked at once.
+    ///
+    /// # Guards
+    /// - Signer must be the pending authority.
+    /// - `threshold` still ≤ signers.len() after the outgoing key leaves.
+    pub fn accept_config_change(ctx: Context<AcceptConfigChange>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
 
-        // ── INTERACTIONS — CPI transfers from vault (invoke_signed) ──
-        let vault_bump = ctx.accounts.config.vault_bump;
-        let vault_seeds: &[&[u8]] = &[b"vault", &[vault_bump]];
-        let signer_seeds: &[&[&[u8]]] = &[vault_seeds];
+        let old_authority = config.authority;
+        let old_treasury = config.treasury;
+        let new_authority = config.pending_authority;
 
-        // vault → player (90 %)
-        if player_payout > 0 {
-            system_program::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.system_program.to_account_info(),
-                    system_program::Transfer {
-                        from: ctx.accounts.vault.to_account_info(),
-                        to: ctx.accounts.player.to_account_info(),
-                    },
-                    signer_seeds,
-                ),
-                player_payout,
-            )?;
+        if new_authority != old_authority {
+            rotate_signer(&mut config.signers, old_authority, new_authority);
         }
+        require!(
+            config.threshold as usize <= config.signers.len(),
+            FlappyError::InvalidSignerSet
+        );
 
-        // vault → treasury (10 %)
-        if fee > 0 {
-            system_program::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.system_program.to_account_info(),
-                    system_program::Transfer {
-                        from: ctx.accounts.vault.to_account_info(),
-                        to: ctx.accounts.treasury.to_account_info(),
-                    },
-                    signer_seeds,
-                ),
-                fee,
-            )?;
+        if config.pending_overlap_secs == 0 {
+            config.previous_authority = Pubkey::default();
+            config.previous_authority_valid_until = 0;
+        } else if new_authority != old_authority {
+            config.previous_authority = old_authority;
+            config.previous_authority_valid_until = Clock::get()?
+                .unix_timestamp
+                .checked_add(config.pending_overlap_secs)
+                .ok_or(FlappyError::MathOverflow)?;
         }
 
-        emit!(SessionCashedOut {
-            player: ctx.accounts.player.key(),
-            amount,
-            fee,
-            player_payout,
-            nonce,
+        config.authority = new_authority;
+        config.treasury = config.pending_treasury;
+        config.pending_authority = Pubkey::default();
+        config.pending_treasury = Pubkey::default();
+        config.pending_overlap_secs = 0;
+
+        emit!(ConfigUpdated {
+            old_authority,
+            new_authority: config.authority,
+            old_treasury,
+            new_treasury: config.treasury,
+            previous_authority_valid_until: config.previous_authority_valid_until,
         });
         Ok(())
     }
 
     // ────────────────────────────────────────────────────────────────────────
-    // force_close_on_death — authority kills a session, no payout
+    // set_signer_set — admin installs the M-of-N cashout signers
     // ────────────────────────────────────────────────────────────────────────
 
-    /// Called by the game authority when a player dies.
-    /// Deposit remains in vault (funds future payouts to winners).
+    /// Replaces the cashout signer set. Every cashout must then carry
+    /// exactly `threshold` Ed25519 signatures from distinct members.
     ///
     /// # Guards
-    /// - Session must be active.
-    /// - Signer must be the stored game authority.
-    pub fn force_close_on_death(ctx: Context<ForceClose>) -> Result<()> {
-        let session = &mut ctx.accounts.session;
-
-        // GUARD: session must be active
-        require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+    /// - Signer must be the stored admin.
+    /// - 1 ≤ threshold ≤ signers.len() ≤ MAX_SIGNERS.
+    /// - No duplicate or default pubkeys.
+    pub fn set_signer_set(
+        ctx: Context<AdminConfig>,
+        signers: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        require!(
+            !signers.is_empty()
+                && signers.len() <= MAX_SIGNERS
+                && threshold >= 1
+                && threshold as usize <= signers.len(),
+            FlappyError::InvalidSignerSet
+        );
+        for (i, key) in signers.iter().enumerate() {
+            require!(*key != Pubkey::default(), FlappyError::InvalidSignerSet);
+            require!(!signers[..i].contains(key), FlappyError::InvalidSignerSet);
+        }
 
-        // Authority signer check is handled by Anchor constraint below.
-        // Close session — no payout, deposit stays in vault.
-        session.status = STATUS_CLOSED;
-        session.max_claimable = 0;
-        session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+        let config = &mut ctx.accounts.config;
+        config.signers = signers;
+        config.threshold = threshold;
 
-        emit!(SessionForceClosed {
-            player: session.player,
-            authority: ctx.accounts.authority.key(),
+        emit!(SignerSetUpdated {
+            signers: config.signers.clone(),
+            threshold,
         });
         Ok(())
     }
-}
 
-// ============================================================================
-// ACCOUNT STRUCTS
-// ============================================================================
+    // ────────────────────────────────────────────────────────────────────────
+    // set_fee_splits — admin installs the cashout fee-split table
+    // ────────────────────────────────────────────────────────────────────────
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    /// Deployer/admin who pays for config account rent.
-    #[account(mut)]
-    pub payer: Signer<'info>,
+    /// Replaces the table cashout fees (after the referral share) are split
+    /// across, for SOL and token sessions alike. An empty table sends the
+    /// whole house fee to `config.treasury`.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - splits.len() ≤ MAX_FEE_SPLITS.
+    /// - Every entry has bps > 0; non-empty tables sum to exactly 10_000.
+    /// - No duplicate or default recipients.
+    pub fn set_fee_splits(ctx: Context<AdminConfig>, splits: Vec<FeeSplit>) -> Result<()> {
+        require!(splits.len() <= MAX_FEE_SPLITS, FlappyError::InvalidFeeSplits);
+        let mut total_bps = 0u64;
+        for (i, split) in splits.iter().enumerate() {
+            require!(
+                split.bps > 0 && split.recipient != Pubkey::default(),
+                FlappyError::InvalidFeeSplits
+            );
+            require!(
+                !splits[..i].iter().any(|s| s.recipient == split.recipient),
+                FlappyError::InvalidFeeSplits
+            );
+            total_bps += split.bps as u64;
+        }
+        require!(
+            splits.is_empty() || total_bps == BPS_DENOMINATOR,
+            FlappyError::InvalidFeeSplits
+        );
 
-    /// Game authority pubkey (server signing key).
-    /// CHECK: Any valid pubkey; stored in config for later verification.
-    pub authority: UncheckedAccount<'info>,
+        let config = &mut ctx.accounts.config;
+        config.fee_splits = splits;
 
-    /// Global config PDA — stores treasury, authority, bumps.
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + VaultConfig::INIT_SPACE,
-        seeds = [b"config"],
-        bump,
-    )]
-    pub config: Account<'info, VaultConfig>,
+        emit!(FeeSplitsUpdated {
+            splits: config.fee_splits.clone(),
+        });
+        Ok(())
+    }
 
-    /// Vault PDA — system-owned, holds deposited SOL.
-    /// Not initialized (no data); just referenced so Anchor records the bump.
-    /// CHECK: Derived from seeds; no data to validate.
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump,
-    )]
-    pub vault: UncheckedAccount<'info>,
+    // ────────────────────────────────────────────────────────────────────────
+    // set_guardian — admin designates the emergency pause key
+    // ────────────────────────────────────────────────────────────────────────
 
-    pub system_program: Program<'info, System>,
-}
+    /// Sets the guardian key that may pause (but not unpause) the program.
+    /// Pass the default pubkey to remove the guardian.
+    pub fn set_guardian(ctx: Context<AdminConfig>, guardian: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let old_guardian = config.guardian;
+        config.guardian = guardian;
 
-#[derive(Accounts)]
+        emit!(GuardianUpdated {
+            old_guardian,
+            new_guardian: guardian,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // pause — guardian or admin halts one or more flows
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets the given `PAUSE_*` bits. Bits already set stay set.
+    ///
+    /// # Guards
+    /// - Signer must be the guardian or the admin.
+    /// - `flags` must be a non-empty subset of PAUSE_ALL.
+    pub fn pause(ctx: Context<Pause>, flags: u8) -> Result<()> {
+        require!(
+            flags != 0 && flags & !PAUSE_ALL == 0,
+            FlappyError::InvalidPauseFlags
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.paused |= flags;
+
+        emit!(PauseUpdated {
+            signer: ctx.accounts.signer.key(),
+            paused: config.paused,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // unpause — admin resumes one or more flows
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Clears the given `PAUSE_*` bits. Admin only — a leaked guardian key
+    /// can halt the program but never resume it.
+    pub fn unpause(ctx: Context<AdminConfig>, flags: u8) -> Result<()> {
+        require!(
+            flags != 0 && flags & !PAUSE_ALL == 0,
+            FlappyError::InvalidPauseFlags
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.paused &= !flags;
+
+        emit!(PauseUpdated {
+            signer: ctx.accounts.admin.key(),
+            paused: config.paused,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_stale_session_timeout — admin tunes the self-refund delay
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets how long an active session must go without a server checkpoint
+    /// before its player may call `reclaim_stale_session`.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `timeout_secs` ≥ MIN_STALE_SESSION_TIMEOUT_SECS.
+    pub fn set_stale_session_timeout(
+        ctx: Context<AdminConfig>,
+        timeout_secs: i64,
+    ) -> Result<()> {
+        require!(
+            timeout_secs >= MIN_STALE_SESSION_TIMEOUT_SECS,
+            FlappyError::InvalidTimeout
+        );
+
+        let config = &mut ctx.accounts.config;
+        let old_timeout_secs = config.stale_session_timeout_secs;
+        config.stale_session_timeout_secs = timeout_secs;
+
+        emit!(StaleSessionTimeoutUpdated {
+            old_timeout_secs,
+            new_timeout_secs: timeout_secs,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_price_feed — admin points USD tiers at a SOL/USD price account
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Replaces the price feed used to convert USD tiers into lamports.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `max_price_age_secs` > 0.
+    pub fn set_price_feed(
+        ctx: Context<AdminConfig>,
+        price_feed: Pubkey,
+        oracle_program: Pubkey,
+        max_price_age_secs: i64,
+    ) -> Result<()> {
+        require!(max_price_age_secs > 0, FlappyError::InvalidTimeout);
+
+        let config = &mut ctx.accounts.config;
+        config.price_feed = price_feed;
+        config.oracle_program = oracle_program;
+        config.max_price_age_secs = max_price_age_secs;
+
+        emit!(PriceFeedUpdated {
+            price_feed,
+            oracle_program,
+            max_price_age_secs,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_message_versions — admin migrates the cashout message layout
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets which cashout message layouts `cashout` accepts and the cluster
+    /// id bound into V2 messages. Enable both while the edge function
+    /// migrates, then drop V1.
+    ///
+    /// # Arguments
+    /// * `versions`   — bitfield of MESSAGE_V1 | MESSAGE_V2.
+    /// * `cluster_id` — genesis hash of the cluster this program runs on.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `versions` non-zero and only known bits.
+    /// - `cluster_id` non-zero whenever V2 is enabled.
+    pub fn set_message_versions(
+        ctx: Context<AdminConfig>,
+        versions: u8,
+        cluster_id: [u8; 32],
+    ) -> Result<()> {
+        require!(
+            versions != 0 && versions & !MESSAGE_ALL == 0,
+            FlappyError::InvalidMessageVersions
+        );
+        require!(
+            versions & MESSAGE_V2 == 0 || cluster_id != [0u8; 32],
+            FlappyError::InvalidMessageVersions
+        );
+
+        let config = &mut ctx.accounts.config;
+        config.message_versions = versions;
+        config.cluster_id = cluster_id;
+
+        emit!(MessageVersionsUpdated {
+            versions,
+            cluster_id,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_max_auth_ttl — admin bounds how far ahead an authorization expires
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets the furthest ahead of the current time a cashout
+    /// authorization's `expiry` may be.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `ttl_secs` > 0.
+    pub fn set_max_auth_ttl(ctx: Context<AdminConfig>, ttl_secs: i64) -> Result<()> {
+        require!(ttl_secs > 0, FlappyError::InvalidTimeout);
+
+        let config = &mut ctx.accounts.config;
+        let old_ttl_secs = config.max_auth_ttl_secs;
+        config.max_auth_ttl_secs = ttl_secs;
+
+        emit!(MaxAuthTtlUpdated {
+            old_ttl_secs,
+            new_ttl_secs: ttl_secs,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_min_reserve — admin sets the vault's reserve floor
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets the lamports the SOL vault must keep on top of rent exemption
+    /// and `total_active_deposits` after every cashout.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    pub fn set_min_reserve(ctx: Context<AdminConfig>, lamports: u64) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let old_reserve_lamports = config.min_reserve_lamports;
+        config.min_reserve_lamports = lamports;
+
+        emit!(MinReserveUpdated {
+            old_reserve_lamports,
+            new_reserve_lamports: lamports,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // set_referral_share — admin adjusts the referrer's cut of the fee
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Sets the share of each cashout fee paid to the player's referrer,
+    /// in basis points of the fee.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - `share_bps` ≤ 10_000.
+    pub fn set_referral_share(ctx: Context<AdminConfig>, share_bps: u16) -> Result<()> {
+        require!(
+            share_bps as u64 <= BPS_DENOMINATOR,
+            FlappyError::InvalidReferralShare
+        );
+
+        let config = &mut ctx.accounts.config;
+        let old_share_bps = config.referral_share_bps;
+        config.referral_share_bps = share_bps;
+
+        emit!(ReferralShareUpdated {
+            old_share_bps,
+            new_share_bps: share_bps,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // withdraw_house_profit — admin sweeps house funds to the treasury
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Moves `lamports` of house profit (forfeited deposits and the like)
+    /// from the SOL vault to `config.treasury`.
+    ///
+    /// Only lamports above the liabilities floor can leave: rent exemption
+    /// + `min_reserve_lamports` + `total_active_deposits`.
+    ///
+    /// # Guards
+    /// - Cashouts not paused (withdrawals move vault funds like cashouts).
+    /// - Signer must be the stored admin.
+    /// - `lamports` > 0.
+    /// - Vault stays at or above the liabilities floor.
+    pub fn withdraw_house_profit(ctx: Context<WithdrawHouseProfit>, lamports: u64) -> Result<()> {
+        let config = &ctx.accounts.config;
+
+        // GUARD: circuit breaker
+        require!(config.paused & PAUSE_CASHOUTS == 0, FlappyError::ProgramPaused);
+
+        require!(lamports > 0, FlappyError::ZeroWithdrawal);
+
+        // GUARD: only funds above liabilities + reserve
+        require_vault_reserve(&ctx.accounts.vault, config, lamports)?;
+
+        // ── INTERACTIONS — vault → treasury (invoke_signed) ──
+        let vault_seeds: &[&[u8]] = &[b"vault", &[config.vault_bump]];
+        let signer_seeds: &[&[&[u8]]] = &[vault_seeds];
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.vault.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            lamports,
+        )?;
+
+        emit!(HouseProfitWithdrawn {
+            admin: ctx.accounts.admin.key(),
+            treasury: ctx.accounts.treasury.key(),
+            lamports,
+            vault_balance: ctx.accounts.vault.lamports(),
+            total_active_deposits: config.total_active_deposits,
+            min_reserve_lamports: config.min_reserve_lamports,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // upsert_tier — admin adds, reprices, or retires a deposit tier
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Adds a tier to the registry, or overwrites the existing entry with
+    /// the same id. Retire a tier by setting `enabled = false`; sessions
+    /// already active on it are unaffected.
+    ///
+    /// # Arguments
+    /// * `id`       — tier id passed to `deposit` / `deposit_token`.
+    /// * `mint`      — deposit currency; default pubkey = native SOL.
+    /// * `amount`    — deposit amount in base units of `mint`.
+    /// * `enabled`   — whether deposits accept this tier.
+    /// * `fee_bps`   — per-tier fee override; `None` uses `config.fee_bps`.
+    /// * `usd_cents` — if non-zero, a USD-priced SOL tier; `amount` unused.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - Exactly one of `amount` / `usd_cents` > 0.
+    /// - USD-priced tiers must be SOL tiers.
+    /// - `fee_bps` ≤ MAX_FEE_BPS.
+    /// - Registry holds at most MAX_TIERS entries.
+    pub fn upsert_tier(
+        ctx: Context<AdminTiers>,
+        id: u8,
+        mint: Pubkey,
+        amount: u64,
+        enabled: bool,
+        fee_bps: Option<u16>,
+        usd_cents: u64,
+    ) -> Result<()> {
+        require!(
+            (amount > 0) != (usd_cents > 0),
+            FlappyError::InvalidTierAmount
+        );
+        require!(
+            usd_cents == 0 || mint == Pubkey::default(),
+            FlappyError::InvalidTierAmount
+        );
+        if let Some(bps) = fee_bps {
+            require!(bps <= MAX_FEE_BPS, FlappyError::FeeTooHigh);
+        }
+
+        let tier = Tier {
+            id,
+            mint,
+            amount,
+            enabled,
+            fee_bps,
+            usd_cents,
+        };
+        let tiers = &mut ctx.accounts.tier_config.tiers;
+        match tiers.iter_mut().find(|t| t.id == id) {
+            Some(existing) => *existing = tier,
+            None => {
+                require!(tiers.len() < MAX_TIERS, FlappyError::TierTableFull);
+                tiers.push(tier);
+            }
+        }
+
+        emit!(TierUpdated {
+            id,
+            mint,
+            amount,
+            enabled,
+            fee_bps,
+            usd_cents,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // add_allowed_mint — admin enables an SPL mint for token deposits
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Allowlists `mint` for token tiers and creates the vault's associated
+    /// token account for it (if it does not exist yet).
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - Mint carries no unsupported Token-2022 extensions.
+    /// - Mint not already allowlisted.
+    /// - Allowlist holds at most MAX_ALLOWED_MINTS entries.
+    pub fn add_allowed_mint(ctx: Context<AddAllowedMint>) -> Result<()> {
+        validate_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+
+        let mint = ctx.accounts.mint.key();
+        let config = &mut ctx.accounts.config;
+        require!(!config.allowed_mints.contains(&mint), FlappyError::InvalidMint);
+        require!(
+            config.allowed_mints.len() < MAX_ALLOWED_MINTS,
+            FlappyError::MintAllowlistFull
+        );
+        config.allowed_mints.push(mint);
+
+        emit!(MintAllowlistUpdated {
+            mint,
+            allowed: true,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // remove_allowed_mint — admin stops new token deposits in a mint
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Removes `mint` from the allowlist. Sessions already active in it can
+    /// still cash out or reclaim; only new deposits are refused.
+    ///
+    /// # Guards
+    /// - Signer must be the stored admin.
+    /// - Mint currently allowlisted.
+    pub fn remove_allowed_mint(ctx: Context<AdminConfig>, mint: Pubkey) -> Result<()> {
+        let mints = &mut ctx.accounts.config.allowed_mints;
+        let pos = mints
+            .iter()
+            .position(|m| *m == mint)
+            .ok_or(FlappyError::MintNotAllowed)?;
+        mints.remove(pos);
+
+        emit!(MintAllowlistUpdated {
+            mint,
+            allowed: false,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // deposit — player enters a game session
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Transfers SOL from player → PDA-controlled vault and activates a session.
+    /// USD-priced tiers are converted to lamports through `price_feed`.
+    ///
+    /// # Arguments
+    /// * `tier`          — TierConfig id to deposit into.
+    /// * `session_index` — which of the player's session slots to use.
+    /// * `max_lamports`  — slippage bound; the most the player agrees to pay.
+    /// * `referrer`      — who referred the player; recorded on the first
+    ///   deposit only and immutable afterwards.
+    ///
+    /// # Guards
+    /// - Deposits not paused.
+    /// - `tier` must be a registered, enabled SOL tier in TierConfig.
+    /// - USD tiers: price feed matches config, is owned by the oracle
+    ///   program, trading, and no older than `max_price_age_secs`.
+    /// - Deposit amount ≤ `max_lamports`.
+    /// - Session must NOT already be active (no double-deposit).
+    /// - `referrer` only on the first deposit (or repeating the recorded
+    ///   one), and never the player.
+    /// - SOL goes to a PDA; no private key can move it.
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        tier: u8,
+        session_index: u8,
+        max_lamports: u64,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        // GUARD: circuit breaker
+        require!(
+            ctx.accounts.config.paused & PAUSE_DEPOSITS == 0,
+            FlappyError::ProgramPaused
+        );
+
+        // GUARD: SOL tier registered and enabled
+        let mut tier_entry = ctx
+            .accounts
+            .tier_config
+            .find_enabled(tier, &Pubkey::default())?
+            .clone();
+
+        // USD tier → lamports at the current oracle price
+        if tier_entry.usd_cents > 0 {
+            let feed = ctx
+                .accounts
+                .price_feed
+                .as_ref()
+                .ok_or(FlappyError::InvalidPriceFeed)?;
+            let price = load_sol_usd_price(
+                &feed.to_account_info(),
+                &ctx.accounts.config,
+                Clock::get()?.unix_timestamp,
+            )?;
+            tier_entry.amount = usd_cents_to_lamports(tier_entry.usd_cents, &price)?;
+        }
+
+        // GUARD: slippage bound from the client
+        require!(
+            tier_entry.amount <= max_lamports,
+            FlappyError::SlippageExceeded
+        );
+
+        // GUARD + EFFECTS: no live session; write session state
+        open_session(
+            &mut ctx.accounts.session,
+            &mut ctx.accounts.player_profile,
+            ctx.accounts.player.key(),
+            session_index,
+            ctx.bumps.session,
+            ctx.bumps.player_profile,
+            &tier_entry,
+            referrer,
+        )?;
+        ctx.accounts.config.record_deposit(tier_entry.amount)?;
+
+        // ── CPI: player → vault (player is signer, no invoke_signed) ──
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.player.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            tier_entry.amount,
+        )?;
+
+        emit!(SessionCreated {
+            player: ctx.accounts.player.key(),
+            session_index,
+            tier,
+            mint: tier_entry.mint,
+            deposit_amount: tier_entry.amount,
+            nonce: ctx.accounts.session.nonce,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // top_up — player adds funds to an active session
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Transfers more SOL from player → vault for a session that is already
+    /// live, adding it to `deposit_amount`.
+    ///
+    /// The nonce is bumped so any authorization issued against the old
+    /// balance can no longer be redeemed.
+    ///
+    /// # Guards
+    /// - Deposits not paused.
+    /// - Session must be active and denominated in SOL.
+    /// - Signer must own the session.
+    /// - `lamports` > 0.
+    pub fn top_up(ctx: Context<TopUp>, lamports: u64) -> Result<()> {
+        // GUARD: circuit breaker
+        require!(
+            ctx.accounts.config.paused & PAUSE_DEPOSITS == 0,
+            FlappyError::ProgramPaused
+        );
+
+        require!(lamports > 0, FlappyError::ZeroTopUp);
+
+        let session = &mut ctx.accounts.session;
+        require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+        require!(
+            session.player == ctx.accounts.player.key(),
+            FlappyError::UnauthorizedPlayer
+        );
+        require!(session.mint == Pubkey::default(), FlappyError::InvalidMint);
+
+        // ── CPI: player → vault (player is signer, no invoke_signed) ──
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.player.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            lamports,
+        )?;
+
+        // Only the part not already covered by earlier claims is a new
+        // liability; the rest is never released by cashout or reclaim.
+        let newly_outstanding = session.credit_top_up(lamports)?;
+        ctx.accounts.config.record_deposit(newly_outstanding)?;
+        // Invalidate authorizations computed against the old balance
+        session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+
+        emit!(SessionToppedUp {
+            player: session.player,
+            session_index: session.index,
+            lamports,
+            deposit_amount: session.deposit_amount,
+            nonce: session.nonce,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // deposit_token — player enters a game session with SPL tokens
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Transfers SPL Token / Token-2022 tokens from the player's token
+    /// account → the vault's associated token account and activates a
+    /// session. For transfer-fee mints the session is credited with the
+    /// amount the vault actually received, not the tier amount.
+    ///
+    /// # Arguments
+    /// * `tier`          — TierConfig id to deposit into.
+    /// * `session_index` — which of the player's session slots to use.
+    /// * `referrer`      — as in `deposit`.
+    ///
+    /// # Guards
+    /// - Deposits not paused.
+    /// - Mint allowlisted in config, with no unsupported extensions.
+    /// - `tier` must be a registered, enabled tier in that mint.
+    /// - Session must NOT already be active (no double-deposit).
+    /// - `referrer` only on the first deposit (or repeating the recorded
+    ///   one), and never the player.
+    pub fn deposit_token(
+        ctx: Context<DepositToken>,
+        tier: u8,
+        session_index: u8,
+        referrer: Option<Pubkey>,
+    ) -> Result<()> {
+        // GUARD: circuit breaker
+        require!(
+            ctx.accounts.config.paused & PAUSE_DEPOSITS == 0,
+            FlappyError::ProgramPaused
+        );
+
+        // GUARD: extensions re-checked in case the mint was allowlisted
+        // before this validation existed
+        validate_mint_extensions(&ctx.accounts.mint.to_account_info())?;
+
+        // GUARD: token tier registered and enabled
+        let mint = ctx.accounts.mint.key();
+        let tier_entry = ctx
+            .accounts
+            .tier_config
+            .find_enabled(tier, &mint)?
+            .clone();
+
+        // GUARD + EFFECTS: no live session; write session state
+        open_session(
+            &mut ctx.accounts.session,
+            &mut ctx.accounts.player_profile,
+            ctx.accounts.player.key(),
+            session_index,
+            ctx.bumps.session,
+            ctx.bumps.player_profile,
+            &tier_entry,
+            referrer,
+        )?;
+
+        // ── CPI: player token account → vault token account ──
+        let balance_before = ctx.accounts.vault_token_account.amount;
+        token_interface::transfer_checked(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token_interface::TransferChecked {
+                    from: ctx.accounts.player_token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault_token_account.to_account_info(),
+                    authority: ctx.accounts.player.to_account_info(),
+                },
+            ),
+            tier_entry.amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        // Credit what actually landed — transfer-fee mints withhold part
+        ctx.accounts.vault_token_account.reload()?;
+        let received =
+            deposit_received(balance_before, ctx.accounts.vault_token_account.amount)?;
+        ctx.accounts.session.deposit_amount = received;
+
+        emit!(SessionCreated {
+            player: ctx.accounts.player.key(),
+            session_index,
+            tier,
+            mint,
+            deposit_amount: received,
+            nonce: ctx.accounts.session.nonce,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // cashout — server-authorized payout
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Pays out earnings to the player (minus `config.fee_bps` to treasury).
+    /// In FEE_MODE_PROFIT the fee applies only to the part of the session's
+    /// cumulative claims above `deposit_amount`, so a break-even cashout
+    /// pays no fee.
+    ///
+    /// When `config.fee_splits` is set, the house fee is divided across its
+    /// recipients instead, passed as writable `remaining_accounts` in table
+    /// order, and `treasury` may be omitted. Each share rounds down and the
+    /// dust goes to the first entry, as does any share too small to leave
+    /// its recipient rent exempt.
+    ///
+    /// `max_claimable` is the server's cumulative ceiling for the session.
+    /// A partial cashout pays `amount` but leaves the session active, so the
+    /// player can keep playing and claim again under a fresh authorization;
+    /// `session.claimed` tracks the running total against the ceiling.
+    ///
+    /// The transaction **must** include `config.threshold` Ed25519 program
+    /// instructions (at any index before this one), each verifying a
+    /// signature from a distinct member of `config.signers` over the
+    /// canonical cashout message — V1 when `issued_at` is `None`, V2 when
+    /// it is set, each only while enabled in `config.message_versions`.
+    /// V2 also binds `amount`, the session PDA, the deposit tier,
+    /// `config.cluster_id`, `issued_at` and `partial`.
+    ///
+    /// # Arguments
+    /// * `amount`        — lamports the player wants now.
+    /// * `max_claimable` — server-authorized cumulative ceiling (signed).
+    /// * `nonce`         — must match session.nonce.
+    /// * `expiry`        — unix timestamp; tx rejected after this.
+    /// * `partial`       — keep the session active after paying out (V2
+    ///   only, as signed).
+    /// * `issued_at`     — unix timestamp a V2 authorization was signed;
+    ///   `None` for V1, which does not sign it.
+    ///
+    /// # Guards (in order)
+    /// 0. Cashouts not paused
+    /// 1. Session active, denominated in SOL
+    /// 2. Signer == session.player
+    /// 3. Nonce match (anti-replay)
+    /// 4. Expiry not passed, at most `max_auth_ttl_secs` ahead;
+    ///    V2: session.started_at ≤ issued_at ≤ now, ± ISSUED_AT_SKEW_SECS
+    /// 5. claimed + amount ≤ max_claimable
+    /// 6. amount > 0; `partial` only with V2
+    /// 7. Ed25519 signatures verified (M distinct signers + message content)
+    /// 8. Auth hash not among the session's consumed ids (replay guard)
+    /// 9. State updated BEFORE transfers (checks-effects-interactions)
+    /// 10. Vault keeps rent exemption + `min_reserve_lamports` +
+    ///     `total_active_deposits` after the payout
+    pub fn cashout<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Cashout<'info>>,
+        amount: u64,
+        max_claimable: u64,
+        nonce: u64,
+        expiry: i64,
+        partial: bool,
+        issued_at: Option<i64>,
+    ) -> Result<()> {
+        // Guards 0–8 + session effects (shared with `cashout_token`)
+        let mut split = settle_cashout(
+            &mut ctx.accounts.session,
+            &ctx.accounts.config,
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.player.key(),
+            &Pubkey::default(),
+            &ctx.accounts.player_profile.referrer,
+            amount,
+            max_claimable,
+            nonce,
+            expiry,
+            partial,
+            issued_at,
+        )?;
+
+        // GUARD: a recorded referrer must be passed so it can be paid
+        require!(
+            ctx.accounts.player_profile.referrer == Pubkey::default()
+                || ctx.accounts.referrer.is_some(),
+            FlappyError::MissingReferrerAccount
+        );
+
+        // Referral share goes to the house if it is too small to leave the
+        // referrer rent exempt
+        if let (Some(referrer), true) = (ctx.accounts.referrer.as_ref(), split.referral_fee > 0) {
+            let lands = payout_lands(
+                &Rent::get()?,
+                referrer.lamports(),
+                referrer.data_len(),
+                split.referral_fee,
+            );
+            if !lands {
+                split.forfeit_referral();
+            }
+        }
+        let CashoutSplit {
+            fee_bps,
+            gross_profit,
+            fee,
+            house_fee,
+            referral_fee,
+            player_payout,
+            claimed_total,
+            released_deposit,
+        } = split;
+
+        // ── EFFECTS — solvency totals ──
+        let config = &mut ctx.accounts.config;
+        config.release_deposit(released_deposit);
+        config.record_payout(player_payout, fee)?;
+
+        // 10. Payout must not eat into live deposits or the reserve floor
+        require_vault_reserve(&ctx.accounts.vault, config, amount)?;
+
+        // ── INTERACTIONS — CPI transfers from vault (invoke_signed) ──
+        let vault_bump = ctx.accounts.config.vault_bump;
+        let vault_seeds: &[&[u8]] = &[b"vault", &[vault_bump]];
+        let signer_seeds: &[&[&[u8]]] = &[vault_seeds];
+
+        // vault → player (amount − fee)
+        if player_payout > 0 {
+            system_program::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.player.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                player_payout,
+            )?;
+        }
+
+        // vault → treasury or fee-split recipients (fee − referral share)
+        let fee_splits = &ctx.accounts.config.fee_splits;
+        if fee_splits.is_empty() {
+            // GUARD: the treasury is required when there is no table
+            let treasury = ctx
+                .accounts
+                .treasury
+                .as_ref()
+                .ok_or(FlappyError::InvalidTreasury)?;
+            if house_fee > 0 {
+                system_program::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.vault.to_account_info(),
+                            to: treasury.to_account_info(),
+                        },
+                        signer_seeds,
+                    ),
+                    house_fee,
+                )?;
+            }
+        } else {
+            // GUARD: one writable account per table entry, in table order
+            require!(
+                ctx.remaining_accounts.len() == fee_splits.len(),
+                FlappyError::InvalidFeeRecipient
+            );
+            for (split, recipient) in fee_splits.iter().zip(ctx.remaining_accounts) {
+                require_keys_eq!(
+                    recipient.key(),
+                    split.recipient,
+                    FlappyError::InvalidFeeRecipient
+                );
+                require!(recipient.is_writable, FlappyError::InvalidFeeRecipient);
+            }
+            // Shares too small to leave their recipient rent exempt go to
+            // the first entry
+            let recipients: Vec<(u64, usize)> = ctx
+                .remaining_accounts
+                .iter()
+                .map(|recipient| (recipient.lamports(), recipient.data_len()))
+                .collect();
+            let shares = route_fee_shares(
+                &Rent::get()?,
+                split_fee(house_fee, fee_splits)?,
+                &recipients,
+            )?;
+            for (share, recipient) in shares.into_iter().zip(ctx.remaining_accounts) {
+                if share > 0 {
+                    system_program::transfer(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.system_program.to_account_info(),
+                            system_program::Transfer {
+                                from: ctx.accounts.vault.to_account_info(),
+                                to: recipient.clone(),
+                            },
+                            signer_seeds,
+                        ),
+                        share,
+                    )?;
+                }
+            }
+        }
+
+        // vault → referrer (referral share)
+        if let (Some(referrer), true) = (ctx.accounts.referrer.as_ref(), referral_fee > 0) {
+            system_program::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: referrer.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                referral_fee,
+            )?;
+        }
+
+        emit!(SessionCashedOut {
+            player: ctx.accounts.player.key(),
+            session_index: ctx.accounts.session.index,
+            mint: Pubkey::default(),
+            amount,
+            gross_profit,
+            fee,
+            fee_bps,
+            referrer: ctx.accounts.player_profile.referrer,
+            referral_fee,
+            player_payout,
+            nonce,
+            claimed_total,
+            partial,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // cashout_token — server-authorized payout of an SPL token session
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Token counterpart of `cashout`: same guards, authorization message
+    /// and fee math, paid from the vault's token account for `session.mint`.
+    /// With transfer-fee mints the vault is debited `amount` and the mint's
+    /// fee is withheld from what the player and fee recipients receive.
+    ///
+    /// When `config.fee_splits` is set, the house fee is divided as in
+    /// `cashout`: each recipient's token account for `session.mint` is
+    /// passed as a writable `remaining_accounts` entry in table order, and
+    /// `treasury_token_account` may be omitted. Token accounts are already
+    /// rent exempt, so every share lands.
+    ///
+    /// # Arguments
+    /// Same as `cashout`; amounts are in base units of the session's mint.
+    pub fn cashout_token<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CashoutToken<'info>>,
+        amount: u64,
+        max_claimable: u64,
+        nonce: u64,
+        expiry: i64,
+        partial: bool,
+        issued_at: Option<i64>,
+    ) -> Result<()> {
+        // Guards 0–8 + session effects (shared with `cashout`)
+        let mint = ctx.accounts.mint.key();
+        let split = settle_cashout(
+            &mut ctx.accounts.session,
+            &ctx.accounts.config,
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.player.key(),
+            &mint,
+            &ctx.accounts.player_profile.referrer,
+            amount,
+            max_claimable,
+            nonce,
+            expiry,
+            partial,
+            issued_at,
+        )?;
+
+        // GUARD: a recorded referrer's token account must be passed so it
+        // can be paid
+        require!(
+            ctx.accounts.player_profile.referrer == Pubkey::default()
+                || ctx.accounts.referrer_token_account.is_some(),
+            FlappyError::MissingReferrerAccount
+        );
+
+        // ── INTERACTIONS — token transfers from vault (invoke_signed) ──
+        let vault_bump = ctx.accounts.config.vault_bump;
+
+        // vault → player (amount − fee)
+        if split.player_payout > 0 {
+            transfer_from_token_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.player_token_account,
+                &ctx.accounts.vault,
+                vault_bump,
+                split.player_payout,
+            )?;
+        }
+
+        // vault → treasury or fee-split recipients (fee − referral share)
+        let fee_splits = &ctx.accounts.config.fee_splits;
+        if fee_splits.is_empty() {
+            // GUARD: the treasury's token account is required when there is
+            // no table
+            let treasury_token_account = ctx
+                .accounts
+                .treasury_token_account
+                .as_ref()
+                .ok_or(FlappyError::InvalidTreasury)?;
+            if split.house_fee > 0 {
+                transfer_from_token_vault(
+                    &ctx.accounts.token_program,
+                    &ctx.accounts.mint,
+                    &ctx.accounts.vault_token_account,
+                    treasury_token_account,
+                    &ctx.accounts.vault,
+                    vault_bump,
+                    split.house_fee,
+                )?;
+            }
+        } else {
+            // GUARD: one writable token account per table entry, in table
+            // order, for this mint and owned by the entry's recipient
+            require!(
+                ctx.remaining_accounts.len() == fee_splits.len(),
+                FlappyError::InvalidFeeRecipient
+            );
+            let shares = split_fee(split.house_fee, fee_splits)?;
+            for ((fee_split, share), account) in
+                fee_splits.iter().zip(shares).zip(ctx.remaining_accounts)
+            {
+                require!(account.is_writable, FlappyError::InvalidFeeRecipient);
+                require_keys_eq!(
+                    *account.owner,
+                    ctx.accounts.token_program.key(),
+                    FlappyError::InvalidFeeRecipient
+                );
+                let recipient_token_account =
+                    InterfaceAccount::<TokenAccount>::try_from(account)?;
+                require_keys_eq!(
+                    recipient_token_account.mint,
+                    mint,
+                    FlappyError::InvalidFeeRecipient
+                );
+                require_keys_eq!(
+                    recipient_token_account.owner,
+                    fee_split.recipient,
+                    FlappyError::InvalidFeeRecipient
+                );
+                if share > 0 {
+                    transfer_from_token_vault(
+                        &ctx.accounts.token_program,
+                        &ctx.accounts.mint,
+                        &ctx.accounts.vault_token_account,
+                        &recipient_token_account,
+                        &ctx.accounts.vault,
+                        vault_bump,
+                        share,
+                    )?;
+                }
+            }
+        }
+
+        // vault → referrer (referral share)
+        if let (Some(referrer_token_account), true) = (
+            ctx.accounts.referrer_token_account.as_ref(),
+            split.referral_fee > 0,
+        ) {
+            transfer_from_token_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.mint,
+                &ctx.accounts.vault_token_account,
+                referrer_token_account,
+                &ctx.accounts.vault,
+                vault_bump,
+                split.referral_fee,
+            )?;
+        }
+
+        emit!(SessionCashedOut {
+            player: ctx.accounts.player.key(),
+            session_index: ctx.accounts.session.index,
+            mint,
+            amount,
+            gross_profit: split.gross_profit,
+            fee: split.fee,
+            fee_bps: split.fee_bps,
+            referrer: ctx.accounts.player_profile.referrer,
+            referral_fee: split.referral_fee,
+            player_payout: split.player_payout,
+            nonce,
+            claimed_total: split.claimed_total,
+            partial,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // force_close_on_death — authority kills a session, no payout
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Called by the game authority when a player dies.
+    /// Deposit remains in vault (funds future payouts to winners).
+    ///
+    /// Increments the victim's death counter and, for PvP kills, the
+    /// killer's kill counter.
+    ///
+    /// # Arguments
+    /// * `killer` — player credited with the kill (bullet deaths only).
+    /// * `cause`  — one of the `DEATH_CAUSE_*` codes.
+    ///
+    /// # Guards
+    /// - Force-closes not paused.
+    /// - Session must be active.
+    /// - Signer must be the game authority (or the previous one while its
+    ///   overlap window is open).
+    /// - `cause` is a known code; a killer requires DEATH_CAUSE_BULLET.
+    /// - Killer differs from the victim and matches `killer_profile`.
+    pub fn force_close_on_death(
+        ctx: Context<ForceClose>,
+        killer: Option<Pubkey>,
+        cause: u8,
+    ) -> Result<()> {
+        // GUARD: circuit breaker
+        require!(
+            ctx.accounts.config.paused & PAUSE_FORCE_CLOSES == 0,
+            FlappyError::ProgramPaused
+        );
+
+        // GUARD: signer is the current or still-valid previous authority
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts
+                .config
+                .is_game_authority(&ctx.accounts.authority.key(), now),
+            FlappyError::UnauthorizedAuthority
+        );
+
+        // GUARD: cause code known
+        require!(is_valid_death_cause(cause), FlappyError::InvalidDeathCause);
+
+        let session = &mut ctx.accounts.session;
+
+        // GUARD: session must be active
+        require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+
+        // GUARD: killer attribution consistent with cause + accounts
+        match killer {
+            Some(killer_key) => {
+                require!(cause == DEATH_CAUSE_BULLET, FlappyError::InvalidDeathCause);
+                require!(killer_key != session.player, FlappyError::InvalidKiller);
+                let killer_profile = ctx
+                    .accounts
+                    .killer_profile
+                    .as_mut()
+                    .ok_or(FlappyError::InvalidKiller)?;
+                require_keys_eq!(killer_profile.player, killer_key, FlappyError::InvalidKiller);
+                killer_profile.kills = killer_profile.kills.saturating_add(1);
+            }
+            None => {
+                require!(
+                    ctx.accounts.killer_profile.is_none(),
+                    FlappyError::InvalidKiller
+                );
+            }
+        }
+
+        // Close session — no payout, deposit stays in vault as house funds.
+        ctx.accounts
+            .config
+            .release_deposit(session.outstanding_deposit());
+        session.status = STATUS_CLOSED;
+        session.max_claimable = 0;
+        session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+
+        let victim_profile = &mut ctx.accounts.victim_profile;
+        victim_profile.deaths = victim_profile.deaths.saturating_add(1);
+
+        emit!(SessionForceClosed {
+            player: session.player,
+            session_index: session.index,
+            authority: ctx.accounts.authority.key(),
+            killer,
+            cause,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // force_close_many — authority kills a batch of sessions, no payout
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Batch form of `force_close_on_death` for end-of-round cleanup.
+    ///
+    /// `remaining_accounts` holds writable `[session, player_profile]` pairs.
+    /// Every closed session records `cause` and a death on the profile; kill
+    /// attribution needs the single-session instruction.
+    /// Sessions that are not active are skipped so one stale entry does not
+    /// abort the batch; an account that is not a valid PDA does.
+    ///
+    /// # Guards
+    /// - Force-closes not paused.
+    /// - Signer must be the game authority (or the previous one while its
+    ///   overlap window is open).
+    /// - `cause` is a known code other than DEATH_CAUSE_BULLET.
+    /// - Each account is owned by this program and sits at its PDA.
+    pub fn force_close_many<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ForceCloseMany<'info>>,
+        cause: u8,
+    ) -> Result<()> {
+        // GUARD: circuit breaker
+        require!(
+            ctx.accounts.config.paused & PAUSE_FORCE_CLOSES == 0,
+            FlappyError::ProgramPaused
+        );
+
+        // GUARD: signer is the current or still-valid previous authority
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts
+                .config
+                .is_game_authority(&ctx.accounts.authority.key(), now),
+            FlappyError::UnauthorizedAuthority
+        );
+
+        // GUARD: cause code known; bullet deaths need a killer
+        require!(
+            is_valid_death_cause(cause) && cause != DEATH_CAUSE_BULLET,
+            FlappyError::InvalidDeathCause
+        );
+
+        // GUARD: accounts come in [session, profile] pairs
+        let pairs = ctx.remaining_accounts.chunks_exact(2);
+        require!(pairs.remainder().is_empty(), FlappyError::InvalidSessionAccount);
+
+        for pair in pairs {
+            let closed = force_close_pair(&mut ctx.accounts.config, &pair[0], &pair[1])?;
+            if let Some(session) = closed {
+                emit!(SessionForceClosed {
+                    player: session.player,
+                    session_index: session.index,
+                    authority: ctx.accounts.authority.key(),
+                    killer: None,
+                    cause,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // close_session — player reclaims Session PDA rent
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Closes a finished Session PDA and returns its rent to the player.
+    ///
+    /// The session nonce is carried into the player's profile so that a
+    /// later `deposit` re-creating the PDA continues the nonce sequence
+    /// instead of restarting at zero.
+    ///
+    /// # Guards
+    /// - Session must be closed (enforced by account constraint).
+    /// - Signer must own the session (enforced by seeds).
+    pub fn close_session(ctx: Context<CloseSession>) -> Result<()> {
+        let session = &ctx.accounts.session;
+        let profile = &mut ctx.accounts.player_profile;
+
+        profile.last_nonce = profile.last_nonce.max(session.nonce);
+
+        emit!(SessionClosed {
+            player: session.player,
+            session_index: session.index,
+            nonce: session.nonce,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // checkpoint_session — authority proves the game server is alive
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Records server liveness for a long-running session, pushing back the
+    /// point at which the player may reclaim it as stale.
+    ///
+    /// # Guards
+    /// - Session must be active.
+    /// - Signer must be the game authority (or the previous one while its
+    ///   overlap window is open).
+    pub fn checkpoint_session(ctx: Context<CheckpointSession>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            ctx.accounts
+                .config
+                .is_game_authority(&ctx.accounts.authority.key(), now),
+            FlappyError::UnauthorizedAuthority
+        );
+
+        let session = &mut ctx.accounts.session;
+        require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+
+        session.last_checkpoint_at = now;
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // reclaim_stale_session — player refund when the server goes silent
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Refunds `deposit_amount` (less anything already taken through partial
+    /// cashouts) from the vault and closes the session once
+    /// `config.stale_session_timeout_secs` has passed since the last server
+    /// checkpoint (or `started_at` if there was none). No signature from the
+    /// game server is needed.
+    ///
+    /// A session can only stay active after a cashout the server signed as
+    /// partial (V2 binds `partial`), so the remaining deposit is still owed.
+    ///
+    /// # Guards
+    /// - Cashouts not paused.
+    /// - Session must be active and denominated in SOL.
+    /// - Signer must own the session.
+    /// - Timeout elapsed since `last_checkpoint_at`.
+    pub fn reclaim_stale_session(ctx: Context<ReclaimStaleSession>) -> Result<()> {
+        // Guards + session effects (shared with `reclaim_stale_token_session`)
+        let refund = settle_reclaim(
+            &mut ctx.accounts.session,
+            &ctx.accounts.config,
+            &ctx.accounts.player.key(),
+            &Pubkey::default(),
+        )?;
+
+        // ── EFFECTS — solvency totals (refund was owed, no reserve check) ──
+        let config = &mut ctx.accounts.config;
+        config.release_deposit(refund);
+        config.record_payout(refund, 0)?;
+
+        // ── INTERACTIONS — vault → player ──
+        let vault_bump = ctx.accounts.config.vault_bump;
+        let vault_seeds: &[&[u8]] = &[b"vault", &[vault_bump]];
+        let signer_seeds: &[&[&[u8]]] = &[vault_seeds];
+
+        if refund > 0 {
+            system_program::transfer(
+                CpiContext::new_with_signer(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.vault.to_account_info(),
+                        to: ctx.accounts.player.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                refund,
+            )?;
+        }
+
+        let session = &ctx.accounts.session;
+        emit!(SessionReclaimed {
+            player: ctx.accounts.player.key(),
+            session_index: session.index,
+            mint: session.mint,
+            refund,
+            started_at: session.started_at,
+            last_checkpoint_at: session.last_checkpoint_at,
+        });
+        Ok(())
+    }
+
+    // ────────────────────────────────────────────────────────────────────────
+    // reclaim_stale_token_session — token refund when the server goes silent
+    // ────────────────────────────────────────────────────────────────────────
+
+    /// Token counterpart of `reclaim_stale_session`: same guards, refund
+    /// paid from the vault's token account for `session.mint`.
+    pub fn reclaim_stale_token_session(ctx: Context<ReclaimStaleTokenSession>) -> Result<()> {
+        // Guards + session effects (shared with `reclaim_stale_session`)
+        let refund = settle_reclaim(
+            &mut ctx.accounts.session,
+            &ctx.accounts.config,
+            &ctx.accounts.player.key(),
+            &ctx.accounts.mint.key(),
+        )?;
+
+        // ── INTERACTIONS — vault token account → player ──
+        if refund > 0 {
+            transfer_from_token_vault(
+                &ctx.accounts.token_program,
+                &ctx.accounts.mint,
+                &ctx.accounts.vault_token_account,
+                &ctx.accounts.player_token_account,
+                &ctx.accounts.vault,
+                ctx.accounts.config.vault_bump,
+                refund,
+            )?;
+        }
+
+        let session = &ctx.accounts.session;
+        emit!(SessionReclaimed {
+            player: ctx.accounts.player.key(),
+            session_index: session.index,
+            mint: session.mint,
+            refund,
+            started_at: session.started_at,
+            last_checkpoint_at: session.last_checkpoint_at,
+        });
+        Ok(())
+    }
+}
+
+// ============================================================================
+// ACCOUNT STRUCTS
+// ============================================================================
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// Deployer/admin who pays for config account rent and funds the vault
+    /// to rent exemption.
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// Game authority pubkey (server signing key).
+    /// CHECK: Any valid pubkey; stored in config for later verification.
+    pub authority: UncheckedAccount<'info>,
+
+    /// Global config PDA — stores treasury, authority, bumps.
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VaultConfig::INIT_SPACE,
+        seeds = [b"config"],
+        bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Deposit tier registry PDA.
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + TierConfig::INIT_SPACE,
+        seeds = [b"tiers"],
+        bump,
+    )]
+    pub tier_config: Account<'info, TierConfig>,
+
+    /// Vault PDA — system-owned, holds deposited SOL.
+    /// Not initialized (no data); just referenced so Anchor records the bump.
+    /// CHECK: Derived from seeds; no data to validate.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// Program upgrade authority — pays for the realloc and tier registry.
+    #[account(mut)]
+    pub upgrade_authority: Signer<'info>,
+
+    /// This program.
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+            @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub program: Program<'info, crate::program::FlappyOne>,
+
+    /// This program's ProgramData — records the upgrade authority.
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(upgrade_authority.key())
+            @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// Config PDA in the pre-upgrade layout; parsed by the handler.
+    /// CHECK: Seeds and owner checked here, layout in `parse_legacy_config`.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        owner = crate::ID,
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    /// Deposit tier registry PDA (did not exist before the upgrade).
+    #[account(
+        init,
+        payer = upgrade_authority,
+        space = 8 + TierConfig::INIT_SPACE,
+        seeds = [b"tiers"],
+        bump,
+    )]
+    pub tier_config: Account<'info, TierConfig>,
+
+    /// Vault PDA — topped up to rent exemption if needed.
+    /// CHECK: Derived from seeds; no data to validate.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    /// Incoming admin — must match config.pending_admin.
+    #[account(
+        constraint = config.pending_admin != Pubkey::default()
+            @ FlappyError::NoPendingAdminChange,
+        constraint = new_admin.key() == config.pending_admin
+            @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub new_admin: Signer<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AdminConfig<'info> {
+    /// Config admin — must match config.admin.
+    #[account(
+        constraint = admin.key() == config.admin @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub admin: Signer<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawHouseProfit<'info> {
+    /// Config admin — must match config.admin.
+    #[account(
+        constraint = admin.key() == config.admin @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub admin: Signer<'info>,
+
+    /// Vault PDA — source of the withdrawal.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Treasury receives the withdrawal.
+    /// CHECK: Verified to match config.treasury via constraint.
+    #[account(
+        mut,
+        constraint = treasury.key() == config.treasury @ FlappyError::InvalidTreasury,
+    )]
+    pub treasury: UncheckedAccount<'info>,
+
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    /// Guardian or admin.
+    #[account(
+        constraint = (signer.key() == config.guardian && config.guardian != Pubkey::default())
+            || signer.key() == config.admin
+            @ FlappyError::UnauthorizedGuardian,
+    )]
+    pub signer: Signer<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptConfigChange<'info> {
+    /// Incoming game authority — must match config.pending_authority.
+    #[account(
+        constraint = config.pending_authority != Pubkey::default()
+            @ FlappyError::NoPendingConfigChange,
+        constraint = new_authority.key() == config.pending_authority
+            @ FlappyError::UnauthorizedAuthority,
+    )]
+    pub new_authority: Signer<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AdminTiers<'info> {
+    /// Config admin — must match config.admin.
+    #[account(
+        constraint = admin.key() == config.admin @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub admin: Signer<'info>,
+
+    /// Program config (read admin).
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Deposit tier registry.
+    #[account(
+        mut,
+        seeds = [b"tiers"],
+        bump = tier_config.bump,
+    )]
+    pub tier_config: Account<'info, TierConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AddAllowedMint<'info> {
+    /// Config admin — must match config.admin; pays for the vault token account.
+    #[account(
+        mut,
+        constraint = admin.key() == config.admin @ FlappyError::UnauthorizedAdmin,
+    )]
+    pub admin: Signer<'info>,
+
+    /// Program config (mutated).
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Mint being allowlisted (SPL Token or Token-2022).
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Vault PDA — authority of the vault token accounts.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Vault's associated token account for `mint` — created if missing.
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8, session_index: u8, max_lamports: u64)]
 pub struct Deposit<'info> {
     /// Player depositing SOL.
     #[account(mut)]
     pub player: Signer<'info>,
 
-    /// Session PDA — created on first deposit, reused on subsequent ones.
-    /// `init_if_needed` creates the account only if it doesn't exist yet.
-    /// On recycled sessions (status=Closed) the fields are overwritten.
-    #[account(
-        init_if_needed,
-        payer = player,
-        space = 8 + Session::INIT_SPACE,
-        seeds = [b"session", player.key().as_ref()],
-        bump,
-    )]
-    pub session: Account<'info, Session>,
+    /// Session PDA — created on first deposit, reused on subsequent ones.
+    /// One per (player, session_index), so a wallet can run several lobbies.
+    /// `init_if_needed` creates the account only if it doesn't exist yet.
+    /// On recycled sessions (status=Closed) the fields are overwritten.
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Session::INIT_SPACE,
+        seeds = [b"session", player.key().as_ref(), &[session_index]],
+        bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Per-player profile — created on first deposit, holds the nonce floor
+    /// and referrer.
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerProfile::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump,
+    )]
+    pub player_profile: Account<'info, PlayerProfile>,
+
+    /// Vault PDA that receives the deposit.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config (read vault_bump, update solvency totals).
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Deposit tier registry (read tier amount + fee).
+    #[account(
+        seeds = [b"tiers"],
+        bump = tier_config.bump,
+    )]
+    pub tier_config: Account<'info, TierConfig>,
+
+    /// SOL/USD price account — required only for USD-priced tiers.
+    /// CHECK: Key, owner and contents validated in `load_sol_usd_price`.
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct TopUp<'info> {
+    /// Player adding funds — must match session.player.
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Player's active session PDA.
+    #[account(
+        mut,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Vault PDA that receives the top-up.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config (read vault_bump, update solvency totals).
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(tier: u8, session_index: u8)]
+pub struct DepositToken<'info> {
+    /// Player depositing tokens.
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Session PDA — same seeds as SOL sessions; see `Deposit`.
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + Session::INIT_SPACE,
+        seeds = [b"session", player.key().as_ref(), &[session_index]],
+        bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Per-player profile — created on first deposit, holds the nonce floor
+    /// and referrer.
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = 8 + PlayerProfile::INIT_SPACE,
+        seeds = [b"player", player.key().as_ref()],
+        bump,
+    )]
+    pub player_profile: Account<'info, PlayerProfile>,
+
+    /// Deposit mint — must be allowlisted in config.
+    #[account(
+        mint::token_program = token_program,
+        constraint = config.allowed_mints.contains(&mint.key()) @ FlappyError::MintNotAllowed,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Player's token account for `mint`.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = player,
+        token::token_program = token_program,
+    )]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault's associated token account for `mint`.
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault PDA — authority of the vault token accounts.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config (read allowlist + vault_bump).
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Deposit tier registry (read tier amount + fee).
+    #[account(
+        seeds = [b"tiers"],
+        bump = tier_config.bump,
+    )]
+    pub tier_config: Account<'info, TierConfig>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Cashout<'info> {
+    /// Player cashing out — must match session.player.
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Player's session PDA.
+    #[account(
+        mut,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Vault PDA — source of payout funds.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Treasury receives the platform fee. Required unless
+    /// `config.fee_splits` routes the fee instead.
+    /// CHECK: Verified to match config.treasury via constraint.
+    #[account(
+        mut,
+        constraint = treasury.key() == config.treasury @ FlappyError::InvalidTreasury,
+    )]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// Player's profile (read referrer).
+    #[account(
+        seeds = [b"player", player.key().as_ref()],
+        bump = player_profile.bump,
+    )]
+    pub player_profile: Account<'info, PlayerProfile>,
+
+    /// Referrer — receives its share of the fee. Required when
+    /// player_profile records one; if the share would leave it below rent
+    /// exemption, the share goes to the house instead.
+    /// CHECK: Verified to match player_profile.referrer via constraint.
+    #[account(
+        mut,
+        constraint = referrer.key() == player_profile.referrer @ FlappyError::InvalidReferrer,
+    )]
+    pub referrer: Option<UncheckedAccount<'info>>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Instructions sysvar — used to read the Ed25519 verification instruction.
+    /// CHECK: Address pinned to the sysvar ID.
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CashoutToken<'info> {
+    /// Player cashing out — must match session.player.
+    pub player: Signer<'info>,
+
+    /// Player's session PDA.
+    #[account(
+        mut,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Session's deposit mint.
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint.key() == session.mint @ FlappyError::InvalidMint,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Player's token account for `mint`.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = player,
+        token::token_program = token_program,
+    )]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault's associated token account for `mint`.
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Treasury's token account for `mint` — receives the fee. Required
+    /// unless `config.fee_splits` routes the fee instead.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = config.treasury,
+        token::token_program = token_program,
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Player's profile (read referrer).
+    #[account(
+        seeds = [b"player", player.key().as_ref()],
+        bump = player_profile.bump,
+    )]
+    pub player_profile: Account<'info, PlayerProfile>,
+
+    /// Referrer's token account for `mint` — receives its share of the
+    /// fee. Required when player_profile records a referrer; clients create
+    /// the associated token account first if it does not exist.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = player_profile.referrer,
+        token::token_program = token_program,
+    )]
+    pub referrer_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Vault PDA — authority of the vault token accounts.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Instructions sysvar — used to read the Ed25519 verification instruction.
+    /// CHECK: Address pinned to the sysvar ID.
+    #[account(address = ix_sysvar::ID)]
+    pub instructions_sysvar: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ForceClose<'info> {
+    /// Game authority — checked against config in the handler, since the
+    /// previous authority's overlap window depends on the clock.
+    pub authority: Signer<'info>,
+
+    /// The player's session to force-close.
+    #[account(
+        mut,
+        seeds = [b"session", session.player.as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    /// Victim's profile — death counter.
+    #[account(
+        mut,
+        seeds = [b"player", session.player.as_ref()],
+        bump = victim_profile.bump,
+    )]
+    pub victim_profile: Account<'info, PlayerProfile>,
+
+    /// Killer's profile — kill counter. Required iff `killer` is Some.
+    #[account(
+        mut,
+        seeds = [b"player", killer_profile.player.as_ref()],
+        bump = killer_profile.bump,
+    )]
+    pub killer_profile: Option<Account<'info, PlayerProfile>>,
+}
+
+#[derive(Accounts)]
+pub struct ForceCloseMany<'info> {
+    /// Game authority — checked against config in the handler.
+    pub authority: Signer<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+    // [session, player_profile] pairs are passed in `remaining_accounts`.
+}
+
+#[derive(Accounts)]
+pub struct CloseSession<'info> {
+    /// Player closing the session — receives the rent.
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Player's finished session PDA.
+    #[account(
+        mut,
+        close = player,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+        constraint = session.status == STATUS_CLOSED @ FlappyError::SessionNotClosed,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Player profile — receives the session nonce.
+    #[account(
+        mut,
+        seeds = [b"player", player.key().as_ref()],
+        bump = player_profile.bump,
+    )]
+    pub player_profile: Account<'info, PlayerProfile>,
+}
+
+#[derive(Accounts)]
+pub struct CheckpointSession<'info> {
+    /// Game authority — checked against config in the handler.
+    pub authority: Signer<'info>,
+
+    /// The player's session to checkpoint.
+    #[account(
+        mut,
+        seeds = [b"session", session.player.as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimStaleSession<'info> {
+    /// Player reclaiming their deposit — must match session.player.
+    #[account(mut)]
+    pub player: Signer<'info>,
+
+    /// Player's session PDA.
+    #[account(
+        mut,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Vault PDA — source of the refund.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        mut,
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimStaleTokenSession<'info> {
+    /// Player reclaiming their deposit — must match session.player.
+    pub player: Signer<'info>,
+
+    /// Player's session PDA.
+    #[account(
+        mut,
+        seeds = [b"session", player.key().as_ref(), &[session.index]],
+        bump = session.bump,
+    )]
+    pub session: Account<'info, Session>,
+
+    /// Session's deposit mint.
+    #[account(
+        mint::token_program = token_program,
+        constraint = mint.key() == session.mint @ FlappyError::InvalidMint,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Player's token account for `mint`.
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = player,
+        token::token_program = token_program,
+    )]
+    pub player_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault's associated token account for `mint`.
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault,
+        associated_token::token_program = token_program,
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Vault PDA — authority of the vault token accounts.
+    /// CHECK: PDA verified by seeds + bump from config.
+    #[account(
+        seeds = [b"vault"],
+        bump = config.vault_bump,
+    )]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Program config.
+    #[account(
+        seeds = [b"config"],
+        bump = config.config_bump,
+    )]
+    pub config: Account<'info, VaultConfig>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// ============================================================================
+// STATE
+// ============================================================================
+
+#[account]
+#[derive(InitSpace)]
+pub struct VaultConfig {
+    /// Wallet that receives platform fees.
+    pub treasury: Pubkey, // 32
+    /// Game server signing key (for cashout auth + death close).
+    pub authority: Pubkey, // 32
+    /// Bump for the vault PDA (seeds = ["vault"]).
+    pub vault_bump: u8, // 1
+    /// Bump for this config PDA (seeds = ["config"]).
+    pub config_bump: u8, // 1
+    /// Key allowed to propose authority / treasury changes.
+    pub admin: Pubkey, // 32
+    /// Authority staged by `propose_config_change` (default = none).
+    pub pending_authority: Pubkey, // 32
+    /// Treasury staged by `propose_config_change` (default = none).
+    pub pending_treasury: Pubkey, // 32
+    /// Active platform fee in basis points (≤ MAX_FEE_BPS).
+    pub fee_bps: u16, // 2
+    /// Key that may set (but not clear) pause bits (default = none).
+    pub guardian: Pubkey, // 32
+    /// Bitfield of PAUSE_DEPOSITS | PAUSE_CASHOUTS | PAUSE_FORCE_CLOSES.
+    pub paused: u8, // 1
+    /// Keys allowed to co-sign cashout authorizations.
+    #[max_len(MAX_SIGNERS)]
+    pub signers: Vec<Pubkey>, // 4 + 5 × 32
+    /// Number of distinct `signers` required per cashout (M of N).
+    pub threshold: u8, // 1
+    /// Authority replaced by the last `accept_config_change`.
+    pub previous_authority: Pubkey, // 32
+    /// Unix timestamp until which `previous_authority` is still accepted.
+    pub previous_authority_valid_until: i64, // 8
+    /// Seconds without a checkpoint before `reclaim_stale_session` opens.
+    pub stale_session_timeout_secs: i64, // 8
+    /// SPL mints accepted by `deposit_token` (vault holds an ATA for each).
+    #[max_len(MAX_ALLOWED_MINTS)]
+    pub allowed_mints: Vec<Pubkey>, // 4 + 4 × 32
+    /// Pyth SOL/USD price account used to price USD tiers.
+    pub price_feed: Pubkey, // 32
+    /// Program that must own `price_feed`.
+    pub oracle_program: Pubkey, // 32
+    /// Maximum age of the price used by `deposit`.
+    pub max_price_age_secs: i64, // 8
+    /// Bitfield of MESSAGE_V1 | MESSAGE_V2 accepted by cashouts.
+    pub message_versions: u8, // 1
+    /// Genesis hash of this cluster, bound into V2 cashout messages.
+    pub cluster_id: [u8; 32], // 32
+    /// Furthest ahead of now a cashout authorization may expire.
+    pub max_auth_ttl_secs: i64, // 8
+    /// Lamports owed to live SOL sessions (deposits less partial claims).
+    pub total_active_deposits: u64, // 8
+    /// Lamports paid to players by SOL cashouts and refunds.
+    pub total_paid_out: u64, // 8
+    /// Lamports paid to the treasury as SOL cashout fees.
+    pub total_fees: u64, // 8
+    /// Lamports the SOL vault keeps on top of rent and active deposits.
+    pub min_reserve_lamports: u64, // 8
+    /// Referrer's share of each cashout fee, in basis points of the fee.
+    pub referral_share_bps: u16, // 2
+    /// Recipients of the SOL cashout house fee (empty = all to treasury).
+    #[max_len(MAX_FEE_SPLITS)]
+    pub fee_splits: Vec<FeeSplit>, // 4 + 4 × 34
+    /// FEE_MODE_GROSS | FEE_MODE_PROFIT — what cashout fees are charged on.
+    pub fee_mode: u8, // 1
+    /// Overlap window staged by `propose_config_change`.
+    pub pending_overlap_secs: i64, // 8
+    /// Admin staged by `propose_admin` (default = none pending).
+    pub pending_admin: Pubkey, // 32
+    // INIT_SPACE = 870
+}
+
+/// VaultConfig as written by the original release, before any field was
+/// appended. Read only by `migrate_config`.
+#[derive(AnchorDeserialize, Debug)]
+struct LegacyVaultConfig {
+    treasury: Pubkey,
+    authority: Pubkey,
+    vault_bump: u8,
+    config_bump: u8,
+}
+
+impl VaultConfig {
+    /// True while the previous authority's overlap window is open.
+    pub fn previous_authority_active(&self, now: i64) -> bool {
+        self.previous_authority != Pubkey::default() && now < self.previous_authority_valid_until
+    }
+
+    /// Current authority, or the previous one during its overlap window.
+    pub fn is_game_authority(&self, key: &Pubkey, now: i64) -> bool {
+        *key == self.authority
+            || (*key == self.previous_authority && self.previous_authority_active(now))
+    }
+
+    /// Adds a SOL deposit to `total_active_deposits`.
+    pub fn record_deposit(&mut self, lamports: u64) -> Result<()> {
+        self.total_active_deposits = self
+            .total_active_deposits
+            .checked_add(lamports)
+            .ok_or(FlappyError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Removes a settled session's outstanding deposit from
+    /// `total_active_deposits`.
+    pub fn release_deposit(&mut self, lamports: u64) {
+        self.total_active_deposits = self.total_active_deposits.saturating_sub(lamports);
+    }
+
+    /// Adds a SOL payout to `total_paid_out` and its fee to `total_fees`.
+    pub fn record_payout(&mut self, player_payout: u64, fee: u64) -> Result<()> {
+        self.total_paid_out = self
+            .total_paid_out
+            .checked_add(player_payout)
+            .ok_or(FlappyError::MathOverflow)?;
+        self.total_fees = self
+            .total_fees
+            .checked_add(fee)
+            .ok_or(FlappyError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct TierConfig {
+    /// Registered deposit tiers, looked up by id in `deposit`.
+    #[max_len(MAX_TIERS)]
+    pub tiers: Vec<Tier>, // 4 + 16 × 53
+    /// PDA bump.
+    pub bump: u8, // 1
+    // INIT_SPACE = 853
+}
+
+impl TierConfig {
+    /// Enabled tier `id` denominated in `mint` (default pubkey = SOL).
+    pub fn find_enabled(&self, id: u8, mint: &Pubkey) -> Result<&Tier> {
+        self.tiers
+            .iter()
+            .find(|t| t.id == id && t.enabled && t.mint == *mint)
+            .ok_or_else(|| error!(FlappyError::InvalidTier))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeSplit {
+    /// Account credited with this share of the house fee.
+    pub recipient: Pubkey, // 32
+    /// Share in basis points; a table's entries sum to 10_000.
+    pub bps: u16, // 2
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Tier {
+    /// Id passed to `deposit` and stored as `Session.deposit_tier`.
+    pub id: u8, // 1
+    /// Deposit currency; default pubkey = native SOL.
+    pub mint: Pubkey, // 32
+    /// Deposit amount in base units of `mint` (lamports for SOL).
+    pub amount: u64, // 8
+    /// Disabled tiers reject new deposits.
+    pub enabled: bool, // 1
+    /// Per-tier fee override in basis points; `None` = `config.fee_bps`.
+    pub fee_bps: Option<u16>, // 3
+    /// USD price in cents (SOL tiers only); 0 = fixed `amount`.
+    pub usd_cents: u64, // 8
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Session {
+    /// Player pubkey.
+    pub player: Pubkey, // 32
+    /// Deposit tier id (key into TierConfig).
+    pub deposit_tier: u8, // 1
+    /// Deposit in lamports (including any `top_up`s).
+    pub deposit_amount: u64, // 8
+    /// 0 = Inactive (fresh), 1 = Active, 2 = Closed.
+    pub status: u8, // 1
+    /// Server-authorized max claimable lamports.
+    pub max_claimable: u64, // 8
+    /// Unix timestamp when session started.
+    pub started_at: i64, // 8
+    /// Monotonic counter; increments on deposit / cashout / death.
+    pub nonce: u64, // 8
+    /// SHA-256 of last consumed authorization (also in `consumed_auth_ids`).
+    pub last_auth_hash: [u8; 32], // 32
+    /// Expiry timestamp of last authorization.
+    pub auth_expiry: i64, // 8
+    /// PDA bump.
+    pub bump: u8, // 1
+    /// Fee override snapshotted from the tier at deposit time.
+    pub tier_fee_bps: Option<u16>, // 3
+    /// Unix timestamp of the last server checkpoint (starts at started_at).
+    pub last_checkpoint_at: i64, // 8
+    /// Session slot index (PDA seed), so one player can hold several.
+    pub index: u8, // 1
+    /// Lamports already paid out (gross) by partial cashouts this session.
+    pub claimed: u64, // 8
+    /// Deposit currency; default pubkey = native SOL.
+    pub mint: Pubkey, // 32
+    /// Ring of recently consumed authorization ids (auth hashes).
+    pub consumed_auth_ids: [[u8; 32]; AUTH_RING_LEN], // 8 × 32
+    /// Next slot in `consumed_auth_ids` to overwrite.
+    pub consumed_auth_cursor: u8, // 1
+    // INIT_SPACE = 416
+}
+
+impl Session {
+    /// Lamports of a live SOL session still counted in
+    /// `VaultConfig.total_active_deposits`; 0 for token sessions.
+    pub fn outstanding_deposit(&self) -> u64 {
+        if self.status != STATUS_ACTIVE || self.mint != Pubkey::default() {
+            return 0;
+        }
+        self.deposit_amount.saturating_sub(self.claimed)
+    }
+
+    /// Adds `lamports` to `deposit_amount` and returns how much
+    /// `outstanding_deposit()` grew by — less than `lamports` when partial
+    /// cashouts have already claimed past the old deposit.
+    pub fn credit_top_up(&mut self, lamports: u64) -> Result<u64> {
+        let before = self.outstanding_deposit();
+        self.deposit_amount = self
+            .deposit_amount
+            .checked_add(lamports)
+            .ok_or(FlappyError::MathOverflow)?;
+        Ok(self.outstanding_deposit() - before)
+    }
+
+    /// True if `auth_id` is among the recently consumed authorizations.
+    pub fn is_auth_consumed(&self, auth_id: &[u8; 32]) -> bool {
+        self.consumed_auth_ids.contains(auth_id)
+    }
+
+    /// Records `auth_id` as consumed, evicting the oldest entry.
+    pub fn record_consumed_auth(&mut self, auth_id: [u8; 32]) {
+        let slot = self.consumed_auth_cursor as usize % AUTH_RING_LEN;
+        self.consumed_auth_ids[slot] = auth_id;
+        self.consumed_auth_cursor = ((slot + 1) % AUTH_RING_LEN) as u8;
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PlayerProfile {
+    /// Player pubkey.
+    pub player: Pubkey, // 32
+    /// Highest session nonce seen by `close_session`; a re-created session
+    /// starts above it.
+    pub last_nonce: u64, // 8
+    /// PDA bump.
+    pub bump: u8, // 1
+    /// PvP kills credited by `force_close_on_death`.
+    pub kills: u64, // 8
+    /// Deaths recorded by `force_close_on_death` / `force_close_many`.
+    pub deaths: u64, // 8
+    /// Player who referred this one, set on the first deposit
+    /// (default = none). Immutable once set.
+    pub referrer: Pubkey, // 32
+    // INIT_SPACE = 89
+}
+
+// ============================================================================
+// EVENTS
+// ============================================================================
+
+#[event]
+pub struct ConfigInitialized {
+    pub treasury: Pubkey,
+    pub authority: Pubkey,
+    pub admin: Pubkey,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct ConfigMigrated {
+    pub treasury: Pubkey,
+    pub authority: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AdminChangeProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminUpdated {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct ConfigChangeProposed {
+    pub admin: Pubkey,
+    pub pending_authority: Pubkey,
+    pub pending_treasury: Pubkey,
+    pub overlap_secs: i64,
+}
+
+#[event]
+pub struct ConfigUpdated {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub old_treasury: Pubkey,
+    pub new_treasury: Pubkey,
+    pub previous_authority_valid_until: i64,
+}
+
+#[event]
+pub struct FeeUpdated {
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
+
+#[event]
+pub struct SignerSetUpdated {
+    pub signers: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct FeeModeUpdated {
+    pub old_fee_mode: u8,
+    pub new_fee_mode: u8,
+}
+
+#[event]
+pub struct FeeSplitsUpdated {
+    pub splits: Vec<FeeSplit>,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub signer: Pubkey,
+    pub paused: u8,
+}
+
+#[event]
+pub struct StaleSessionTimeoutUpdated {
+    pub old_timeout_secs: i64,
+    pub new_timeout_secs: i64,
+}
+
+#[event]
+pub struct MaxAuthTtlUpdated {
+    pub old_ttl_secs: i64,
+    pub new_ttl_secs: i64,
+}
+
+#[event]
+pub struct MinReserveUpdated {
+    pub old_reserve_lamports: u64,
+    pub new_reserve_lamports: u64,
+}
+
+#[event]
+pub struct ReferralShareUpdated {
+    pub old_share_bps: u16,
+    pub new_share_bps: u16,
+}
+
+#[event]
+pub struct HouseProfitWithdrawn {
+    pub admin: Pubkey,
+    pub treasury: Pubkey,
+    pub lamports: u64,
+    /// Vault balance after the withdrawal.
+    pub vault_balance: u64,
+    pub total_active_deposits: u64,
+    pub min_reserve_lamports: u64,
+}
+
+#[event]
+pub struct TierUpdated {
+    pub id: u8,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub enabled: bool,
+    pub fee_bps: Option<u16>,
+    pub usd_cents: u64,
+}
+
+#[event]
+pub struct MessageVersionsUpdated {
+    pub versions: u8,
+    pub cluster_id: [u8; 32],
+}
+
+#[event]
+pub struct PriceFeedUpdated {
+    pub price_feed: Pubkey,
+    pub oracle_program: Pubkey,
+    pub max_price_age_secs: i64,
+}
+
+#[event]
+pub struct MintAllowlistUpdated {
+    pub mint: Pubkey,
+    pub allowed: bool,
+}
+
+#[event]
+pub struct SessionCreated {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub tier: u8,
+    pub mint: Pubkey,
+    pub deposit_amount: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct SessionToppedUp {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub lamports: u64,
+    pub deposit_amount: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct SessionCashedOut {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// Part of `amount` above the session's deposit.
+    pub gross_profit: u64,
+    /// Charged on `amount` or `gross_profit`, per `VaultConfig.fee_mode`.
+    pub fee: u64,
+    pub fee_bps: u16,
+    /// Default pubkey = no referrer.
+    pub referrer: Pubkey,
+    /// Part of `fee` paid to `referrer`.
+    pub referral_fee: u64,
+    pub player_payout: u64,
+    pub nonce: u64,
+    pub claimed_total: u64,
+    pub partial: bool,
+}
+
+#[event]
+pub struct SessionForceClosed {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub authority: Pubkey,
+    pub killer: Option<Pubkey>,
+    pub cause: u8,
+}
+
+#[event]
+pub struct SessionClosed {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub nonce: u64,
+}
 
-    /// Vault PDA that receives the deposit.
-    /// CHECK: PDA verified by seeds + bump from config.
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump = config.vault_bump,
-    )]
-    pub vault: UncheckedAccount<'info>,
+#[event]
+pub struct SessionReclaimed {
+    pub player: Pubkey,
+    pub session_index: u8,
+    pub mint: Pubkey,
+    pub refund: u64,
+    pub started_at: i64,
+    pub last_checkpoint_at: i64,
+}
 
-    /// Program config (read vault_bump).
-    #[account(
-        seeds = [b"config"],
-        bump = config.config_bump,
-    )]
-    pub config: Account<'info, VaultConfig>,
+// ============================================================================
+// ERRORS
+// ============================================================================
 
-    pub system_program: Program<'info, System>,
+#[error_code]
+pub enum FlappyError {
+    #[msg("Deposit tier is unknown or disabled.")]
+    InvalidTier,
+    #[msg("Session is already active. Cannot deposit again.")]
+    SessionAlreadyActive,
+    #[msg("Session is not active.")]
+    SessionNotActive,
+    #[msg("Signer is not the session player.")]
+    UnauthorizedPlayer,
+    #[msg("Caller is not the game authority.")]
+    UnauthorizedAuthority,
+    #[msg("Cashout amount exceeds authorized maximum.")]
+    AmountExceedsAuthorized,
+    #[msg("Authorization nonce does not match session nonce.")]
+    InvalidNonce,
+    #[msg("Cashout authorization has expired.")]
+    AuthorizationExpired,
+    #[msg("Cashout amount must be greater than zero.")]
+    ZeroCashout,
+    #[msg("Missing Ed25519 signature verification instruction.")]
+    MissingEd25519Instruction,
+    #[msg("Invalid Ed25519 instruction format.")]
+    InvalidEd25519Instruction,
+    #[msg("Ed25519 pubkey is not a configured cashout signer.")]
+    InvalidAuthority,
+    #[msg("Authorization message does not match expected parameters.")]
+    InvalidAuthorizationMessage,
+    #[msg("Replay detected: this authorization was already used.")]
+    ReplayDetected,
+    #[msg("Math overflow in fee calculation.")]
+    MathOverflow,
+    #[msg("Treasury account does not match config.")]
+    InvalidTreasury,
+    #[msg("Caller is not the config admin.")]
+    UnauthorizedAdmin,
+    #[msg("No config change is pending.")]
+    NoPendingConfigChange,
+    #[msg("Proposed authority or treasury is invalid.")]
+    InvalidConfigChange,
+    #[msg("Fee exceeds the hard cap.")]
+    FeeTooHigh,
+    #[msg("Tier deposit amount must be greater than zero.")]
+    InvalidTierAmount,
+    #[msg("Tier registry is full.")]
+    TierTableFull,
+    #[msg("This operation is currently paused.")]
+    ProgramPaused,
+    #[msg("Caller is not the guardian or admin.")]
+    UnauthorizedGuardian,
+    #[msg("Pause flags are empty or contain unknown bits.")]
+    InvalidPauseFlags,
+    #[msg("Signer set or threshold is invalid.")]
+    InvalidSignerSet,
+    #[msg("The same signer appears more than once.")]
+    DuplicateSigner,
+    #[msg("Number of authorization signatures does not match the threshold.")]
+    SignerThresholdMismatch,
+    #[msg("Session has not been idle long enough to reclaim.")]
+    SessionNotStale,
+    #[msg("Timeout is below the allowed minimum.")]
+    InvalidTimeout,
+    #[msg("Session must be closed before its account can be closed.")]
+    SessionNotClosed,
+    #[msg("Account is not a valid writable Session PDA.")]
+    InvalidSessionAccount,
+    #[msg("Unknown death cause, or cause inconsistent with killer.")]
+    InvalidDeathCause,
+    #[msg("Killer is the victim or does not match the killer profile.")]
+    InvalidKiller,
+    #[msg("Account is not a valid writable PlayerProfile PDA.")]
+    InvalidProfileAccount,
+    #[msg("Top-up amount must be greater than zero.")]
+    ZeroTopUp,
+    #[msg("Mint is not allowlisted for token deposits.")]
+    MintNotAllowed,
+    #[msg("Mint allowlist is full.")]
+    MintAllowlistFull,
+    #[msg("Mint does not match the session or is already allowlisted.")]
+    InvalidMint,
+    #[msg("Mints with a transfer hook are not supported.")]
+    UnsupportedTransferHook,
+    #[msg("Mints with a permanent delegate are not supported.")]
+    UnsupportedPermanentDelegate,
+    #[msg("Mint carries a Token-2022 extension that is not supported.")]
+    UnsupportedMintExtension,
+    #[msg("Price feed is missing, not the configured feed, or malformed.")]
+    InvalidPriceFeed,
+    #[msg("Price feed is older than the configured maximum age.")]
+    StalePrice,
+    #[msg("Price feed is not trading or reports a non-positive price.")]
+    InvalidPrice,
+    #[msg("Deposit amount exceeds the caller's max_lamports bound.")]
+    SlippageExceeded,
+    #[msg("Message version flags are invalid, or V2 lacks a cluster id.")]
+    InvalidMessageVersions,
+    #[msg("Authorization expiry is further ahead than max_auth_ttl_secs.")]
+    AuthorizationTooLong,
+    #[msg("Authorization was issued before the session started or in the future.")]
+    InvalidIssuedAt,
+    #[msg("Payout would drop the vault below its reserve or rent exemption.")]
+    InsufficientVaultFunds,
+    #[msg("Withdrawal amount must be greater than zero.")]
+    ZeroWithdrawal,
+    #[msg("Referral share must be at most 10_000 basis points of the fee.")]
+    InvalidReferralShare,
+    #[msg("Referrer is the player, missing, or does not match the profile.")]
+    InvalidReferrer,
+    #[msg("Referrer can only be set on the first deposit and never changed.")]
+    ReferrerAlreadySet,
+    #[msg("Fee splits must be non-zero, distinct, and sum to 10_000 bps.")]
+    InvalidFeeSplits,
+    #[msg("Fee recipient accounts do not match the fee-split table.")]
+    InvalidFeeRecipient,
+    #[msg("Unknown fee mode.")]
+    InvalidFeeMode,
+    #[msg("Price confidence interval is too wide relative to the price.")]
+    PriceTooUncertain,
+    #[msg("Config account is not in the pre-upgrade layout.")]
+    InvalidLegacyConfig,
+    #[msg("No admin change is pending.")]
+    NoPendingAdminChange,
+    #[msg("Partial cashouts need a V2 authorization, which signs `partial`.")]
+    PartialRequiresV2,
+    #[msg("The player's referrer must be passed to receive its fee share.")]
+    MissingReferrerAccount,
 }
 
-#[derive(Accounts)]
-pub struct Cashout<'info> {
-    /// Player cashing out — must match session.player.
-    #[account(mut)]
-    pub player: Signer<'info>,
+// ============================================================================
+// HELPERS
+// ============================================================================
 
-    /// Player's session PDA.
-    #[account(
-        mut,
-        seeds = [b"session", player.key().as_ref()],
-        bump = session.bump,
-    )]
-    pub session: Account<'info, Session>,
+/// Fee split of a settled cashout.
+struct CashoutSplit {
+    fee_bps: u16,
+    /// Part of `amount` above the session's deposit (see `cashout_profit`).
+    gross_profit: u64,
+    /// Total fee: `house_fee + referral_fee`.
+    fee: u64,
+    /// Fee left after the referral share; routed through
+    /// `config.fee_splits` or to the treasury.
+    house_fee: u64,
+    referral_fee: u64,
+    player_payout: u64,
+    claimed_total: u64,
+    /// Drop in the session's `outstanding_deposit` (SOL sessions only).
+    released_deposit: u64,
+}
 
-    /// Vault PDA — source of payout funds.
-    /// CHECK: PDA verified by seeds + bump from config.
-    #[account(
-        mut,
-        seeds = [b"vault"],
-        bump = config.vault_bump,
-    )]
-    pub vault: UncheckedAccount<'info>,
+impl CashoutSplit {
+    /// Hands the referral share to the house when it cannot be paid out.
+    fn forfeit_referral(&mut self) {
+        self.house_fee += self.referral_fee;
+        self.referral_fee = 0;
+    }
+}
 
-    /// Treasury receives the 10 % fee.
-    /// CHECK: Verified to match config.treasury via constraint.
-    #[account(
-        mut,
-        constraint = treasury.key() == config.treasury @ FlappyError::InvalidTreasury,
-    )]
-    pub treasury: UncheckedAccount<'info>,
+/// Opens (or re-opens) `session` on `tier` for `player`, recording
+/// `referrer` if this is the player's first deposit.
+///
+/// Shared by `deposit` and `deposit_token`; the caller moves the funds.
+#[allow(clippy::too_many_arguments)]
+fn open_session(
+    session: &mut Session,
+    profile: &mut PlayerProfile,
+    player: Pubkey,
+    session_index: u8,
+    session_bump: u8,
+    profile_bump: u8,
+    tier: &Tier,
+    referrer: Option<Pubkey>,
+) -> Result<()> {
+    let first_deposit = profile.player == Pubkey::default();
+    if first_deposit {
+        profile.player = player;
+        profile.bump = profile_bump;
+    }
 
-    /// Program config.
-    #[account(
-        seeds = [b"config"],
-        bump = config.config_bump,
-    )]
-    pub config: Account<'info, VaultConfig>,
+    // GUARD: referrer is set once, on the first deposit, and never self
+    record_referrer(profile, referrer, first_deposit)?;
 
-    /// Instructions sysvar — used to read the Ed25519 verification instruction.
-    /// CHECK: Address pinned to the sysvar ID.
-    #[account(address = ix_sysvar::ID)]
-    pub instructions_sysvar: UncheckedAccount<'info>,
+    // GUARD: prevent double-deposit while a session is live.
+    // On a brand-new account (init_if_needed just created it) player == default.
+    // On a recycled account status must be Closed (not Active).
+    if session.player != Pubkey::default() {
+        require!(
+            session.status != STATUS_ACTIVE,
+            FlappyError::SessionAlreadyActive
+        );
+    } else {
+        // Fresh PDA — possibly re-created after `close_session`. Resume
+        // from the profile's nonce floor so old authorizations stay dead.
+        session.nonce = profile.last_nonce;
+    }
 
-    pub system_program: Program<'info, System>,
+    // ── Write session state ──
+    session.player = player;
+    session.index = session_index;
+    session.deposit_tier = tier.id;
+    session.mint = tier.mint;
+    session.deposit_amount = tier.amount;
+    session.tier_fee_bps = tier.fee_bps;
+    session.status = STATUS_ACTIVE;
+    session.max_claimable = 0; // server sets via cashout auth
+    session.claimed = 0;
+    session.started_at = Clock::get()?.unix_timestamp;
+    session.last_checkpoint_at = session.started_at;
+    // Increment nonce to invalidate any stale authorizations
+    session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+    session.last_auth_hash = [0u8; 32];
+    session.auth_expiry = 0;
+    session.bump = session_bump;
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct ForceClose<'info> {
-    /// Game authority — must match config.authority.
-    #[account(
-        constraint = authority.key() == config.authority @ FlappyError::UnauthorizedAuthority,
-    )]
-    pub authority: Signer<'info>,
+/// Runs the cashout guards against `session` and applies its state
+/// changes, returning the fee split for the caller to pay out.
+///
+/// Shared by `cashout` and `cashout_token`; `mint` is the currency the
+/// caller pays out in (default pubkey = SOL) and `referrer` the player's
+/// recorded referrer (default pubkey = none).
+#[allow(clippy::too_many_arguments)]
+fn settle_cashout(
+    session: &mut Account<Session>,
+    config: &VaultConfig,
+    instructions_sysvar: &AccountInfo,
+    player: &Pubkey,
+    mint: &Pubkey,
+    referrer: &Pubkey,
+    amount: u64,
+    max_claimable: u64,
+    nonce: u64,
+    expiry: i64,
+    partial: bool,
+    issued_at: Option<i64>,
+) -> Result<CashoutSplit> {
+    // 0. Circuit breaker
+    require!(config.paused & PAUSE_CASHOUTS == 0, FlappyError::ProgramPaused);
 
-    /// The player's session to force-close.
-    #[account(
-        mut,
-        seeds = [b"session", session.player.as_ref()],
-        bump = session.bump,
-    )]
-    pub session: Account<'info, Session>,
+    // 1. Session must be active, in the currency being paid out
+    require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+    require!(session.mint == *mint, FlappyError::InvalidMint);
 
-    /// Program config.
-    #[account(
-        seeds = [b"config"],
-        bump = config.config_bump,
-    )]
-    pub config: Account<'info, VaultConfig>,
+    // 2. Signer must own the session
+    require!(session.player == *player, FlappyError::UnauthorizedPlayer);
+
+    // 3. Nonce must match — prevents replaying old authorizations
+    require!(nonce == session.nonce, FlappyError::InvalidNonce);
+
+    // 4. Authorization must not be expired, nor expire too far ahead, and
+    //    (V2) must have been issued during this session
+    let clock = Clock::get()?;
+    require!(clock.unix_timestamp < expiry, FlappyError::AuthorizationExpired);
+    let max_expiry = clock
+        .unix_timestamp
+        .checked_add(config.max_auth_ttl_secs)
+        .ok_or(FlappyError::MathOverflow)?;
+    require!(expiry <= max_expiry, FlappyError::AuthorizationTooLong);
+    if let Some(issued_at) = issued_at {
+        require!(
+            issued_at_in_window(issued_at, session.started_at, clock.unix_timestamp),
+            FlappyError::InvalidIssuedAt
+        );
+    }
+
+    // 5. Cumulative claims within authorized ceiling
+    let claimed_total = session
+        .claimed
+        .checked_add(amount)
+        .ok_or(FlappyError::MathOverflow)?;
+    require!(
+        claimed_total <= max_claimable,
+        FlappyError::AmountExceedsAuthorized
+    );
+
+    // 6. No zero-amount cashouts; partial only under V2, which signs it
+    require!(amount > 0, FlappyError::ZeroCashout);
+    require!(
+        !partial || issued_at.is_some(),
+        FlappyError::PartialRequiresV2
+    );
+
+    // 7. Verify Ed25519 signatures via instructions sysvar, over the
+    //    message layout the caller selected (V2 iff `issued_at` is set),
+    //    if the config accepts it
+    //    (layouts live in the shared `flappy-one-auth` crate)
+    let mut accepted_msgs = Vec::with_capacity(1);
+    if let Some(issued_at) = issued_at {
+        if config.message_versions & MESSAGE_V2 != 0 {
+            let msg = CashoutMessageV2 {
+                player: player.to_bytes(),
+                session: session.key().to_bytes(),
+                session_index: session.index,
+                deposit_tier: session.deposit_tier,
+                mint: mint.to_bytes(),
+                amount,
+                max_claimable,
+                nonce,
+                expiry,
+                program_id: crate::id().to_bytes(),
+                cluster_id: config.cluster_id,
+                issued_at,
+                partial,
+            };
+            accepted_msgs.push(msg.to_bytes());
+        }
+    } else if config.message_versions & MESSAGE_V1 != 0 {
+        let msg = CashoutMessageV1 {
+            player: player.to_bytes(),
+            session_index: session.index,
+            mint: mint.to_bytes(),
+            max_claimable,
+            nonce,
+            expiry,
+            program_id: crate::id().to_bytes(),
+        };
+        accepted_msgs.push(msg.to_bytes());
+    }
+    verify_ed25519_signature(instructions_sysvar, config, clock.unix_timestamp, &accepted_msgs)?;
+
+    // 8. Replay check — auth hash must not have been consumed before
+    let auth_hash = hash(&flappy_one_auth::auth_hash_preimage(
+        &player.to_bytes(),
+        session.index,
+        &mint.to_bytes(),
+        max_claimable,
+        nonce,
+        expiry,
+        partial,
+    ))
+    .to_bytes();
+    require!(!session.is_auth_consumed(&auth_hash), FlappyError::ReplayDetected);
+
+    // ── EFFECTS — update state before any transfers ──
+    let outstanding_before = session.outstanding_deposit();
+    let claimed_before = session.claimed;
+    if !partial {
+        session.status = STATUS_CLOSED;
+    }
+    session.claimed = claimed_total;
+    session.max_claimable = max_claimable;
+    session.last_auth_hash = auth_hash;
+    session.record_consumed_auth(auth_hash);
+    session.auth_expiry = expiry;
+    session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+    let released_deposit = outstanding_before.saturating_sub(session.outstanding_deposit());
+
+    // ── FEE MATH ──
+    let gross_profit = cashout_profit(session.deposit_amount, claimed_before, claimed_total);
+    let fee_base = if config.fee_mode == FEE_MODE_PROFIT {
+        gross_profit
+    } else {
+        amount
+    };
+    let fee_bps = session.tier_fee_bps.unwrap_or(config.fee_bps);
+    let fee = fee_base
+        .checked_mul(fee_bps as u64)
+        .ok_or(FlappyError::MathOverflow)?
+        .checked_div(BPS_DENOMINATOR)
+        .ok_or(FlappyError::MathOverflow)?;
+    let player_payout = amount
+        .checked_sub(fee)
+        .ok_or(FlappyError::MathOverflow)?;
+
+    let referral_fee = referral_share(fee, config.referral_share_bps, referrer)?;
+    let house_fee = fee
+        .checked_sub(referral_fee)
+        .ok_or(FlappyError::MathOverflow)?;
+
+    Ok(CashoutSplit {
+        fee_bps,
+        gross_profit,
+        fee,
+        house_fee,
+        referral_fee,
+        player_payout,
+        claimed_total,
+        released_deposit,
+    })
 }
 
-// ============================================================================
-// STATE
-// ============================================================================
+/// VaultConfig as `initialize` creates it: 1-of-1 signer set with
+/// `authority`, V1 messages only, default fee / limits, empty tables.
+fn initial_config(
+    treasury: Pubkey,
+    authority: Pubkey,
+    admin: Pubkey,
+    vault_bump: u8,
+    config_bump: u8,
+    price_feed: Pubkey,
+    oracle_program: Pubkey,
+) -> VaultConfig {
+    VaultConfig {
+        treasury,
+        authority,
+        vault_bump,
+        config_bump,
+        admin,
+        pending_authority: Pubkey::default(),
+        pending_treasury: Pubkey::default(),
+        fee_bps: DEFAULT_FEE_BPS,
+        guardian: Pubkey::default(),
+        paused: 0,
+        // 1-of-1 with the server key until the admin installs a signer set.
+        signers: vec![authority],
+        threshold: 1,
+        previous_authority: Pubkey::default(),
+        previous_authority_valid_until: 0,
+        stale_session_timeout_secs: DEFAULT_STALE_SESSION_TIMEOUT_SECS,
+        allowed_mints: Vec::new(),
+        price_feed,
+        oracle_program,
+        max_price_age_secs: DEFAULT_MAX_PRICE_AGE_SECS,
+        // V1 only until the admin records the cluster id and enables V2.
+        message_versions: MESSAGE_V1,
+        cluster_id: [0u8; 32],
+        max_auth_ttl_secs: DEFAULT_MAX_AUTH_TTL_SECS,
+        total_active_deposits: 0,
+        total_paid_out: 0,
+        total_fees: 0,
+        min_reserve_lamports: 0,
+        referral_share_bps: DEFAULT_REFERRAL_SHARE_BPS,
+        fee_splits: Vec::new(),
+        fee_mode: FEE_MODE_GROSS,
+        pending_overlap_secs: 0,
+        pending_admin: Pubkey::default(),
+    }
+}
 
-#[account]
-#[derive(InitSpace)]
-pub struct VaultConfig {
-    /// Wallet that receives 10 % fees.
-    pub treasury: Pubkey, // 32
-    /// Game server signing key (for cashout auth + death close).
-    pub authority: Pubkey, // 32
-    /// Bump for the vault PDA (seeds = ["vault"]).
-    pub vault_bump: u8, // 1
-    /// Bump for this config PDA (seeds = ["config"]).
-    pub config_bump: u8, // 1
-    // INIT_SPACE = 66
+/// The $1 / $5 / $20 SOL tiers, priced through the oracle.
+fn default_tiers() -> Vec<Tier> {
+    DEFAULT_TIERS
+        .iter()
+        .map(|&(id, usd_cents)| Tier {
+            id,
+            mint: Pubkey::default(),
+            amount: 0,
+            enabled: true,
+            fee_bps: None,
+            usd_cents,
+        })
+        .collect()
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct Session {
-    /// Player pubkey.
-    pub player: Pubkey, // 32
-    /// Deposit tier (1 | 5 | 20).
-    pub deposit_tier: u8, // 1
-    /// Deposit in lamports.
-    pub deposit_amount: u64, // 8
-    /// 0 = Inactive (fresh), 1 = Active, 2 = Closed.
-    pub status: u8, // 1
-    /// Server-authorized max claimable lamports.
-    pub max_claimable: u64, // 8
-    /// Unix timestamp when session started.
-    pub started_at: i64, // 8
-    /// Monotonic counter; increments on deposit / cashout / death.
-    pub nonce: u64, // 8
-    /// SHA-256 of last consumed authorization (replay guard).
-    pub last_auth_hash: [u8; 32], // 32
-    /// Expiry timestamp of last authorization.
-    pub auth_expiry: i64, // 8
-    /// PDA bump.
-    pub bump: u8, // 1
-    // INIT_SPACE = 107
+/// Reads a config account still in the original release's layout:
+/// VaultConfig discriminator followed by exactly `LegacyVaultConfig`.
+fn parse_legacy_config(data: &[u8]) -> Result<LegacyVaultConfig> {
+    require!(
+        data.len() == 8 + LEGACY_CONFIG_LEN && data[..8] == VaultConfig::DISCRIMINATOR,
+        FlappyError::InvalidLegacyConfig
+    );
+    LegacyVaultConfig::deserialize(&mut &data[8..])
+        .map_err(|_| error!(FlappyError::InvalidLegacyConfig))
+}
+
+/// Tops the vault up to rent exemption from `payer`.
+fn fund_vault_rent<'info>(
+    payer: &Signer<'info>,
+    vault: &UncheckedAccount<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let top_up = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(vault.lamports());
+    if top_up > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: vault.to_account_info(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    Ok(())
+}
+
+/// Fails if moving `outflow` lamports out of the SOL vault would leave it
+/// below rent exemption + `min_reserve_lamports` + `total_active_deposits`.
+///
+/// Call after the solvency totals reflect the payout being made.
+fn require_vault_reserve(vault: &AccountInfo, config: &VaultConfig, outflow: u64) -> Result<()> {
+    check_vault_reserve(&Rent::get()?, vault.lamports(), config, outflow)
+}
+
+/// `require_vault_reserve` against an explicit rent and vault balance.
+fn check_vault_reserve(
+    rent: &Rent,
+    balance: u64,
+    config: &VaultConfig,
+    outflow: u64,
+) -> Result<()> {
+    let floor = rent
+        .minimum_balance(0)
+        .checked_add(config.min_reserve_lamports)
+        .and_then(|f| f.checked_add(config.total_active_deposits))
+        .ok_or(FlappyError::MathOverflow)?;
+    let remaining = balance
+        .checked_sub(outflow)
+        .ok_or(FlappyError::InsufficientVaultFunds)?;
+    require!(remaining >= floor, FlappyError::InsufficientVaultFunds);
+    Ok(())
+}
+
+/// Profit realised by a cashout that raises a session's cumulative claims
+/// from `claimed_before` to `claimed_total`: the part above `deposit`.
+///
+/// Summed over partial cashouts this equals `claimed − deposit` (or zero),
+/// however the claims were split.
+fn cashout_profit(deposit: u64, claimed_before: u64, claimed_total: u64) -> u64 {
+    claimed_total
+        .saturating_sub(deposit)
+        .saturating_sub(claimed_before.saturating_sub(deposit))
+}
+
+/// Records `referrer` on `profile` (whose `player` is already set).
+///
+/// Only the first deposit may set it; later deposits may repeat the
+/// recorded referrer but never change or add one. A player cannot refer
+/// themselves.
+fn record_referrer(
+    profile: &mut PlayerProfile,
+    referrer: Option<Pubkey>,
+    first_deposit: bool,
+) -> Result<()> {
+    let Some(referrer) = referrer else {
+        return Ok(());
+    };
+    require!(
+        referrer != profile.player && referrer != Pubkey::default(),
+        FlappyError::InvalidReferrer
+    );
+    if first_deposit {
+        profile.referrer = referrer;
+    } else {
+        require_keys_eq!(profile.referrer, referrer, FlappyError::ReferrerAlreadySet);
+    }
+    Ok(())
+}
+
+/// Referrer's cut of `fee`: `fee × share_bps / 10_000`, rounded down so the
+/// remainder stays with the house; 0 without a referrer.
+fn referral_share(fee: u64, share_bps: u16, referrer: &Pubkey) -> Result<u64> {
+    if *referrer == Pubkey::default() {
+        return Ok(0);
+    }
+    let share = fee
+        .checked_mul(share_bps as u64)
+        .ok_or(FlappyError::MathOverflow)?
+        / BPS_DENOMINATOR;
+    Ok(share)
+}
+
+/// True if crediting `lamports` to an account holding `balance` lamports
+/// and `data_len` bytes leaves it rent exempt. A transfer that would leave
+/// an unfunded wallet below the minimum fails, so such a payout is not
+/// attempted.
+fn payout_lands(rent: &Rent, balance: u64, data_len: usize, lamports: u64) -> bool {
+    balance
+        .checked_add(lamports)
+        .is_some_and(|total| rent.is_exempt(total, data_len))
+}
+
+/// True if a V2 `issued_at` falls within the session's lifetime so far:
+/// `started_at ≤ issued_at ≤ now`, each bound widened by
+/// ISSUED_AT_SKEW_SECS for signer / cluster clock drift.
+fn issued_at_in_window(issued_at: i64, started_at: i64, now: i64) -> bool {
+    issued_at >= started_at.saturating_sub(ISSUED_AT_SKEW_SECS)
+        && issued_at <= now.saturating_add(ISSUED_AT_SKEW_SECS)
+}
+
+/// Divides `fee` across `splits` by basis points.
+///
+/// Each share is `fee × bps / 10_000` rounded down; the dust left over
+/// goes to the first entry, so the shares always sum to exactly `fee`.
+fn split_fee(fee: u64, splits: &[FeeSplit]) -> Result<Vec<u64>> {
+    let mut shares = splits
+        .iter()
+        .map(|split| {
+            let share = fee as u128 * split.bps as u128 / BPS_DENOMINATOR as u128;
+            u64::try_from(share).map_err(|_| error!(FlappyError::MathOverflow))
+        })
+        .collect::<Result<Vec<u64>>>()?;
+    let paid = shares.iter().try_fold(0u64, |acc, s| acc.checked_add(*s));
+    let dust = paid
+        .and_then(|paid| fee.checked_sub(paid))
+        .ok_or(FlappyError::MathOverflow)?;
+    if let Some(first) = shares.first_mut() {
+        *first = first.checked_add(dust).ok_or(FlappyError::MathOverflow)?;
+    }
+    Ok(shares)
+}
+
+/// Moves fee-split shares that would leave their recipient below the
+/// rent-exempt minimum onto the first entry. `recipients` holds each
+/// entry's `(balance, data_len)` in table order. If the first entry cannot
+/// take its total either, that share stays in the vault.
+fn route_fee_shares(
+    rent: &Rent,
+    mut shares: Vec<u64>,
+    recipients: &[(u64, usize)],
+) -> Result<Vec<u64>> {
+    for i in 1..shares.len() {
+        let (balance, data_len) = recipients[i];
+        if shares[i] > 0 && !payout_lands(rent, balance, data_len, shares[i]) {
+            shares[0] = shares[0]
+                .checked_add(shares[i])
+                .ok_or(FlappyError::MathOverflow)?;
+            shares[i] = 0;
+        }
+    }
+    if let (Some(first), Some(&(balance, data_len))) = (shares.first_mut(), recipients.first()) {
+        if *first > 0 && !payout_lands(rent, balance, data_len, *first) {
+            *first = 0;
+        }
+    }
+    Ok(shares)
+}
+
+/// Runs the stale-session guards against `session` and closes it,
+/// returning the refund for the caller to pay out.
+///
+/// Shared by `reclaim_stale_session` and `reclaim_stale_token_session`.
+fn settle_reclaim(
+    session: &mut Session,
+    config: &VaultConfig,
+    player: &Pubkey,
+    mint: &Pubkey,
+) -> Result<u64> {
+    // GUARD: circuit breaker (refunds move vault funds like cashouts)
+    require!(config.paused & PAUSE_CASHOUTS == 0, FlappyError::ProgramPaused);
+
+    require!(session.status == STATUS_ACTIVE, FlappyError::SessionNotActive);
+    require!(session.mint == *mint, FlappyError::InvalidMint);
+    require!(session.player == *player, FlappyError::UnauthorizedPlayer);
+
+    // GUARD: server silent for at least the configured timeout
+    let now = Clock::get()?.unix_timestamp;
+    let stale_at = session
+        .last_checkpoint_at
+        .max(session.started_at)
+        .checked_add(config.stale_session_timeout_secs)
+        .ok_or(FlappyError::MathOverflow)?;
+    require!(now >= stale_at, FlappyError::SessionNotStale);
+
+    // ── EFFECTS ──
+    let refund = session.deposit_amount.saturating_sub(session.claimed);
+    session.status = STATUS_CLOSED;
+    session.max_claimable = 0;
+    session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+    Ok(refund)
+}
+
+/// Validates one `[session, player_profile]` pair from `force_close_many`
+/// and closes the session, recording a death on the profile. Returns the
+/// closed session, or `None` if it was not active (including a session
+/// listed twice, which the first pair already closed).
+fn force_close_pair<'info>(
+    config: &mut VaultConfig,
+    session_info: &'info AccountInfo<'info>,
+    profile_info: &'info AccountInfo<'info>,
+) -> Result<Option<Session>> {
+    // GUARD: owner + discriminator (Account::try_from) and writable
+    require!(session_info.is_writable, FlappyError::InvalidSessionAccount);
+    let mut session = Account::<Session>::try_from(session_info)
+        .map_err(|_| error!(FlappyError::InvalidSessionAccount))?;
+
+    // GUARD: account sits at the canonical Session PDA
+    let expected = Pubkey::create_program_address(
+        &[
+            b"session",
+            session.player.as_ref(),
+            &[session.index],
+            &[session.bump],
+        ],
+        &crate::id(),
+    )
+    .map_err(|_| error!(FlappyError::InvalidSessionAccount))?;
+    require_keys_eq!(
+        expected,
+        session_info.key(),
+        FlappyError::InvalidSessionAccount
+    );
+
+    // Already closed (or never opened) — skip, don't abort the batch.
+    if session.status != STATUS_ACTIVE {
+        return Ok(None);
+    }
+
+    // GUARD: profile is the session player's PlayerProfile PDA
+    require!(profile_info.is_writable, FlappyError::InvalidProfileAccount);
+    let mut profile = Account::<PlayerProfile>::try_from(profile_info)
+        .map_err(|_| error!(FlappyError::InvalidProfileAccount))?;
+    require_keys_eq!(
+        profile.player,
+        session.player,
+        FlappyError::InvalidProfileAccount
+    );
+    let expected_profile = Pubkey::create_program_address(
+        &[b"player", profile.player.as_ref(), &[profile.bump]],
+        &crate::id(),
+    )
+    .map_err(|_| error!(FlappyError::InvalidProfileAccount))?;
+    require_keys_eq!(
+        expected_profile,
+        profile_info.key(),
+        FlappyError::InvalidProfileAccount
+    );
+
+    config.release_deposit(session.outstanding_deposit());
+    session.status = STATUS_CLOSED;
+    session.max_claimable = 0;
+    session.nonce = session.nonce.checked_add(1).unwrap_or(1);
+    session.exit(&crate::id())?;
+
+    profile.deaths = profile.deaths.saturating_add(1);
+    profile.exit(&crate::id())?;
+
+    Ok(Some(session.into_inner()))
 }
 
-// ============================================================================
-// EVENTS
-// ============================================================================
+/// Moves `amount` of the vault's tokens to `to`, signed by the vault PDA.
+fn transfer_from_token_vault<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    vault_token_account: &InterfaceAccount<'info, TokenAccount>,
+    to: &InterfaceAccount<'info, TokenAccount>,
+    vault: &UncheckedAccount<'info>,
+    vault_bump: u8,
+    amount: u64,
+) -> Result<()> {
+    let vault_seeds: &[&[u8]] = &[b"vault", &[vault_bump]];
+    let signer_seeds: &[&[&[u8]]] = &[vault_seeds];
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token_interface::TransferChecked {
+                from: vault_token_account.to_account_info(),
+                mint: mint.to_account_info(),
+                to: to.to_account_info(),
+                authority: vault.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        mint.decimals,
+    )
+}
 
-#[event]
-pub struct ConfigInitialized {
-    pub treasury: Pubkey,
-    pub authority: Pubkey,
+/// SOL/USD price read from a Pyth price account: `price × 10^expo` USD.
+#[derive(Debug)]
+struct OraclePrice {
+    price: i64,
+    expo: i32,
 }
 
-#[event]
-pub struct SessionCreated {
-    pub player: Pubkey,
-    pub tier: u8,
-    pub deposit_lamports: u64,
-    pub nonce: u64,
+/// Price, confidence and publish time as read from either account format.
+struct FeedQuote {
+    price: i64,
+    conf: u64,
+    expo: i32,
+    publish_time: i64,
 }
 
-#[event]
-pub struct SessionCashedOut {
-    pub player: Pubkey,
-    pub amount: u64,
-    pub fee: u64,
-    pub player_payout: u64,
-    pub nonce: u64,
+/// Validates `feed` against the configured price feed and reads its price.
+///
+/// Accepts a Pyth pull-oracle `PriceUpdateV2` account (fully verified) or
+/// a legacy Pyth v2 price account (aggregate status Trading).
+///
+/// Checks, in order: key == `config.price_feed`, owner ==
+/// `config.oracle_program`, account layout, positive price, confidence
+/// interval within MAX_PRICE_CONF_BPS of the price, and publish time within
+/// `config.max_price_age_secs` of `now`.
+fn load_sol_usd_price(feed: &AccountInfo, config: &VaultConfig, now: i64) -> Result<OraclePrice> {
+    require!(
+        config.price_feed != Pubkey::default() && feed.key() == config.price_feed,
+        FlappyError::InvalidPriceFeed
+    );
+    require!(
+        *feed.owner == config.oracle_program,
+        FlappyError::InvalidPriceFeed
+    );
+
+    let data = feed.try_borrow_data()?;
+    let quote = if data.starts_with(&PRICE_UPDATE_V2_DISCRIMINATOR) {
+        parse_price_update_v2(&data)?
+    } else {
+        parse_legacy_price_account(&data)?
+    };
+
+    require!(quote.price > 0, FlappyError::InvalidPrice);
+    require!(
+        quote.conf as u128 * BPS_DENOMINATOR as u128
+            <= quote.price as u128 * MAX_PRICE_CONF_BPS as u128,
+        FlappyError::PriceTooUncertain
+    );
+    require!(
+        now.saturating_sub(quote.publish_time) <= config.max_price_age_secs,
+        FlappyError::StalePrice
+    );
+
+    Ok(OraclePrice {
+        price: quote.price,
+        expo: quote.expo,
+    })
 }
 
-#[event]
-pub struct SessionForceClosed {
-    pub player: Pubkey,
-    pub authority: Pubkey,
+/// Reads `N` bytes at `offset` (caller has checked the length).
+fn le_bytes<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    bytes.copy_from_slice(&data[offset..offset + N]);
+    bytes
 }
 
-// ============================================================================
-// ERRORS
-// ============================================================================
+/// Parses a Pyth receiver `PriceUpdateV2` account (Borsh):
+///    [0..8]    discriminator
+///    [8..40]   write_authority     Pubkey
+///   [40]       verification_level  1 = Full (Partial is rejected)
+///   [41..73]   feed_id             [u8; 32]
+///   [73..81]   price               i64
+///   [81..89]   conf                u64
+///   [89..93]   exponent            i32
+///   [93..101]  publish_time        i64
+///  [101..133]  prev_publish_time, ema_price, ema_conf, posted_slot
+fn parse_price_update_v2(data: &[u8]) -> Result<FeedQuote> {
+    require!(
+        data.len() >= PRICE_UPDATE_V2_MIN_LEN,
+        FlappyError::InvalidPriceFeed
+    );
+    require!(
+        data[40] == PRICE_UPDATE_V2_VERIFICATION_FULL,
+        FlappyError::InvalidPriceFeed
+    );
+    Ok(FeedQuote {
+        price: i64::from_le_bytes(le_bytes(data, 73)),
+        conf: u64::from_le_bytes(le_bytes(data, 81)),
+        expo: i32::from_le_bytes(le_bytes(data, 89)),
+        publish_time: i64::from_le_bytes(le_bytes(data, 93)),
+    })
+}
 
-#[error_code]
-pub enum FlappyError {
-    #[msg("Invalid deposit tier. Must be 1, 5, or 20.")]
-    InvalidTier,
-    #[msg("Session is already active. Cannot deposit again.")]
-    SessionAlreadyActive,
-    #[msg("Session is not active.")]
-    SessionNotActive,
-    #[msg("Signer is not the session player.")]
-    UnauthorizedPlayer,
-    #[msg("Caller is not the game authority.")]
-    UnauthorizedAuthority,
-    #[msg("Cashout amount exceeds authorized maximum.")]
-    AmountExceedsAuthorized,
-    #[msg("Authorization nonce does not match session nonce.")]
-    InvalidNonce,
-    #[msg("Cashout authorization has expired.")]
-    AuthorizationExpired,
-    #[msg("Cashout amount must be greater than zero.")]
-    ZeroCashout,
-    #[msg("Missing Ed25519 signature verification instruction.")]
-    MissingEd25519Instruction,
-    #[msg("Invalid Ed25519 instruction format.")]
-    InvalidEd25519Instruction,
-    #[msg("Ed25519 pubkey does not match game authority.")]
-    InvalidAuthority,
-    #[msg("Authorization message does not match expected parameters.")]
-    InvalidAuthorizationMessage,
-    #[msg("Replay detected: this authorization was already used.")]
-    ReplayDetected,
-    #[msg("Math overflow in fee calculation.")]
-    MathOverflow,
-    #[msg("Treasury account does not match config.")]
-    InvalidTreasury,
+/// Parses a legacy Pyth v2 price account (little-endian, repr(C)):
+///    [0..4]   magic        u32
+///    [4..8]   version      u32
+///    [8..12]  account type u32
+///   [20..24]  expo         i32
+///   [96..104] timestamp    i64  (publish time of `agg`)
+///  [208..216] agg.price    i64
+///  [216..224] agg.conf     u64
+///  [224..228] agg.status   u32
+fn parse_legacy_price_account(data: &[u8]) -> Result<FeedQuote> {
+    require!(
+        data.len() >= PYTH_PRICE_ACCOUNT_MIN_LEN,
+        FlappyError::InvalidPriceFeed
+    );
+    let read_u32 = |o: usize| u32::from_le_bytes(le_bytes(data, o));
+
+    require!(read_u32(0) == PYTH_MAGIC, FlappyError::InvalidPriceFeed);
+    require!(read_u32(4) == PYTH_VERSION_2, FlappyError::InvalidPriceFeed);
+    require!(
+        read_u32(8) == PYTH_ACCOUNT_TYPE_PRICE,
+        FlappyError::InvalidPriceFeed
+    );
+    require!(read_u32(224) == PYTH_STATUS_TRADING, FlappyError::InvalidPrice);
+
+    Ok(FeedQuote {
+        price: i64::from_le_bytes(le_bytes(data, 208)),
+        conf: u64::from_le_bytes(le_bytes(data, 216)),
+        expo: i32::from_le_bytes(le_bytes(data, 20)),
+        publish_time: i64::from_le_bytes(le_bytes(data, 96)),
+    })
 }
 
-// ============================================================================
-// HELPERS
-// ============================================================================
+/// Converts a USD-cent amount into lamports at `price`, rounding up so the
+/// vault never receives less than the tier's dollar value.
+///
+///   lamports = usd_cents × 10^9 / (100 × price × 10^expo)
+///            = usd_cents × 10^(7 − expo) / price
+fn usd_cents_to_lamports(usd_cents: u64, price: &OraclePrice) -> Result<u64> {
+    let scale = 7i64 - price.expo as i64;
+    let pow = |e: i64| {
+        u32::try_from(e)
+            .ok()
+            .and_then(|e| 10u128.checked_pow(e))
+            .ok_or(FlappyError::MathOverflow)
+    };
+    let (numerator, denominator) = if scale >= 0 {
+        (
+            (usd_cents as u128)
+                .checked_mul(pow(scale)?)
+                .ok_or(FlappyError::MathOverflow)?,
+            price.price as u128,
+        )
+    } else {
+        (
+            usd_cents as u128,
+            (price.price as u128)
+                .checked_mul(pow(-scale)?)
+                .ok_or(FlappyError::MathOverflow)?,
+        )
+    };
+    let lamports = numerator
+        .checked_add(denominator - 1)
+        .ok_or(FlappyError::MathOverflow)?
+        / denominator;
+    u64::try_from(lamports).map_err(|_| error!(FlappyError::MathOverflow))
+}
+
+/// Rejects mints carrying Token-2022 extensions the vault can't safely hold.
+///
+/// Legacy SPL Token mints have no extensions and always pass. Transfer fees
+/// are handled (deposits credit the amount received); metadata, group and
+/// interest-bearing extensions don't touch balances. Anything that lets a
+/// third party move, freeze, intercept or re-create vault funds is refused.
+fn validate_mint_extensions(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in state.get_extension_types()? {
+        match extension {
+            ExtensionType::TransferHook => return err!(FlappyError::UnsupportedTransferHook),
+            ExtensionType::PermanentDelegate => {
+                return err!(FlappyError::UnsupportedPermanentDelegate)
+            }
+            ExtensionType::TransferFeeConfig
+            | ExtensionType::InterestBearingConfig
+            | ExtensionType::MetadataPointer
+            | ExtensionType::TokenMetadata
+            | ExtensionType::GroupPointer
+            | ExtensionType::TokenGroup
+            | ExtensionType::GroupMemberPointer
+            | ExtensionType::TokenGroupMember => {}
+            _ => return err!(FlappyError::UnsupportedMintExtension),
+        }
+    }
+    Ok(())
+}
+
+/// Tokens a deposit actually credited to the vault: its balance after the
+/// transfer minus before. Less than the tier amount for transfer-fee mints.
+fn deposit_received(balance_before: u64, balance_after: u64) -> Result<u64> {
+    balance_after
+        .checked_sub(balance_before)
+        .ok_or_else(|| error!(FlappyError::MathOverflow))
+}
 
-/// Scans instructions preceding the current one for a valid Ed25519
-/// verification instruction from the expected authority over the expected
-/// cashout message.
+/// Scans instructions preceding the current one for Ed25519 verification
+/// instructions from the configured signer set over one of the accepted
+/// cashout messages.
 ///
 /// Security model:
 ///   The Ed25519 native program already verified the cryptographic signature
 ///   when the transaction was processed. If the signature were invalid the
 ///   transaction would have aborted before reaching our program. We therefore
 ///   only need to confirm:
-///     (a) An Ed25519 instruction exists.
-///     (b) Exactly one such instruction exists (prevent confusion attacks).
-///     (c) The public key inside it matches our stored authority.
-///     (d) The message inside it matches the expected cashout parameters.
+///     (a) At least one Ed25519 instruction exists.
+///     (b) Each carries exactly one signature (prevent confusion attacks).
+///     (c) Each public key is a configured signer, with no duplicates.
+///         During the overlap window the previous authority stands in for
+///         the current one (and counts as the same signer).
+///     (d) Each message matches an accepted cashout message layout.
 ///     (e) All data is embedded in the instruction itself (index = 0xFFFF).
+///     (f) Exactly `threshold` distinct signers were found.
 fn verify_ed25519_signature(
     instructions_sysvar: &AccountInfo,
-    expected_authority: &Pubkey,
-    player: &Pubkey,
-    max_claimable: u64,
-    nonce: u64,
-    expiry: i64,
-    program_id: &Pubkey,
+    config: &VaultConfig,
+    now: i64,
+    accepted_msgs: &[Vec<u8>],
 ) -> Result<()> {
     let current_ix_index = ix_sysvar::load_current_index_checked(instructions_sysvar)
         .map_err(|_| error!(FlappyError::MissingEd25519Instruction))?;
 
-    let mut found = false;
+    let mut signers: Vec<Pubkey> = Vec::with_capacity(config.signers.len());
 
     for i in 0..current_ix_index as usize {
         let ix = ix_sysvar::load_instruction_at_checked(i, instructions_sysvar)
@@ -598,114 +4208,757 @@ fn verify_ed25519_signature(
             continue; // skip non-Ed25519 instructions (e.g. ComputeBudget)
         }
 
-        // (b) Only one Ed25519 instruction allowed
-        require!(!found, FlappyError::InvalidEd25519Instruction);
-        found = true;
-
-        let data = &ix.data;
-
-        // Minimum size: 2 (header) + 14 (offsets) = 16, plus signature+pk+msg
-        require!(data.len() > 112, FlappyError::InvalidEd25519Instruction);
-
-        // Exactly 1 signature
-        require!(data[0] == 1, FlappyError::InvalidEd25519Instruction);
-
-        // ── Parse Ed25519SignatureOffsets (14 bytes at offset 2) ──
-        //  [2..4]  signature_offset          u16 LE
-        //  [4..6]  signature_instruction_idx  u16 LE
-        //  [6..8]  public_key_offset         u16 LE
-        //  [8..10] public_key_instruction_idx u16 LE
-        // [10..12] message_data_offset       u16 LE
-        // [12..14] message_data_size         u16 LE
-        // [14..16] message_instruction_idx   u16 LE
-        let _sig_offset = u16::from_le_bytes([data[2], data[3]]) as usize;
-        let sig_ix_idx = u16::from_le_bytes([data[4], data[5]]);
-        let pk_offset = u16::from_le_bytes([data[6], data[7]]) as usize;
-        let pk_ix_idx = u16::from_le_bytes([data[8], data[9]]);
-        let msg_offset = u16::from_le_bytes([data[10], data[11]]) as usize;
-        let msg_size = u16::from_le_bytes([data[12], data[13]]) as usize;
-        let msg_ix_idx = u16::from_le_bytes([data[14], data[15]]);
-
-        // (e) All data must be embedded in this instruction (0xFFFF)
-        require!(sig_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
-        require!(pk_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
-        require!(msg_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
-
-        // Bounds checks
-        require!(
-            data.len() >= pk_offset.saturating_add(32),
-            FlappyError::InvalidEd25519Instruction
-        );
-        require!(
-            data.len() >= msg_offset.saturating_add(msg_size),
-            FlappyError::InvalidEd25519Instruction
-        );
-
-        // (c) Public key must match authority
-        let pk_bytes = &data[pk_offset..pk_offset + 32];
-        require!(
-            pk_bytes == expected_authority.to_bytes().as_ref(),
-            FlappyError::InvalidAuthority
-        );
+        let (signer, message) = parse_ed25519_instruction(&ix.data)?;
 
         // (d) Message must match expected cashout authorization
-        let message = &data[msg_offset..msg_offset + msg_size];
-        let expected_msg =
-            build_cashout_message(player, max_claimable, nonce, expiry, program_id);
         require!(
-            message.len() == expected_msg.len(),
-            FlappyError::InvalidAuthorizationMessage
-        );
-        require!(
-            message == expected_msg.as_slice(),
+            accepted_msgs.iter().any(|m| m.as_slice() == message),
             FlappyError::InvalidAuthorizationMessage
         );
+        signers.push(signer);
+    }
+
+    // (a), (c), (f)
+    tally_signers(config, now, &signers)
+}
+
+/// Checks the signer keys of a cashout's Ed25519 instructions against the
+/// configured M-of-N set:
+///   (a) at least one signature,
+///   (c) every key a member, none twice — the previous authority stands
+///       in for the current one during its overlap window,
+///   (f) exactly `threshold` distinct signers.
+fn tally_signers(config: &VaultConfig, now: i64, signers: &[Pubkey]) -> Result<()> {
+    let previous_active = config.previous_authority_active(now);
+    let mut seen: Vec<Pubkey> = Vec::with_capacity(signers.len());
+
+    for &key in signers {
+        let signer = if previous_active && key == config.previous_authority {
+            config.authority
+        } else {
+            key
+        };
+
+        // (c) Public key must be a configured signer, counted once
+        require!(config.signers.contains(&signer), FlappyError::InvalidAuthority);
+        require!(!seen.contains(&signer), FlappyError::DuplicateSigner);
+        seen.push(signer);
     }
 
-    // (a) Must have found exactly one Ed25519 instruction
-    require!(found, FlappyError::MissingEd25519Instruction);
+    // (a) Must have found at least one Ed25519 instruction
+    require!(!seen.is_empty(), FlappyError::MissingEd25519Instruction);
+
+    // (f) Exactly M distinct signers
+    require!(
+        seen.len() == config.threshold as usize,
+        FlappyError::SignerThresholdMismatch
+    );
 
     Ok(())
 }
 
-/// Builds the canonical 108-byte cashout authorization message.
-///
-/// Layout (all fixed-width, no length ambiguity):
-///   [ 0..20)  DOMAIN_SEPARATOR       "FLAPPYONE_CASHOUT_V1"
-///   [20..52)  player pubkey          32 bytes
-///   [52..60)  max_claimable          u64 LE
-///   [60..68)  nonce                  u64 LE
-///   [68..76)  expiry                 i64 LE
-///   [76..108) program_id             32 bytes
-fn build_cashout_message(
-    player: &Pubkey,
-    max_claimable: u64,
-    nonce: u64,
-    expiry: i64,
-    program_id: &Pubkey,
-) -> Vec<u8> {
-    let mut msg = Vec::with_capacity(108);
-    msg.extend_from_slice(DOMAIN_SEPARATOR); //  20
-    msg.extend_from_slice(player.as_ref()); //  32
-    msg.extend_from_slice(&max_claimable.to_le_bytes()); //   8
-    msg.extend_from_slice(&nonce.to_le_bytes()); //   8
-    msg.extend_from_slice(&expiry.to_le_bytes()); //   8
-    msg.extend_from_slice(program_id.as_ref()); //  32
-    msg // 108
-}
-
-/// SHA-256 hash of authorization parameters.
-/// Stored in `session.last_auth_hash` to prevent replaying the same auth.
-fn compute_auth_hash(
-    player: &Pubkey,
-    max_claimable: u64,
-    nonce: u64,
-    expiry: i64,
-) -> [u8; 32] {
-    let mut data = Vec::with_capacity(56);
-    data.extend_from_slice(player.as_ref()); // 32
-    data.extend_from_slice(&max_claimable.to_le_bytes()); //  8
-    data.extend_from_slice(&nonce.to_le_bytes()); //  8
-    data.extend_from_slice(&expiry.to_le_bytes()); //  8
-    hash::hash(&data).to_bytes()
+/// Removes `old` from the cashout signer set on an authority rotation:
+/// `new` takes its slot, or the slot is dropped if `new` is already a
+/// member.
+fn rotate_signer(signers: &mut Vec<Pubkey>, old: Pubkey, new: Pubkey) {
+    if signers.contains(&new) {
+        signers.retain(|k| *k != old);
+    } else if let Some(slot) = signers.iter_mut().find(|k| **k == old) {
+        *slot = new;
+    }
+}
+
+/// Parses a single-signature Ed25519 program instruction and returns the
+/// signer pubkey and the signed message.
+fn parse_ed25519_instruction(data: &[u8]) -> Result<(Pubkey, &[u8])> {
+    // Minimum size: 2 (header) + 14 (offsets) = 16, plus signature+pk+msg
+    require!(data.len() > 112, FlappyError::InvalidEd25519Instruction);
+
+    // (b) Exactly 1 signature
+    require!(data[0] == 1, FlappyError::InvalidEd25519Instruction);
+
+    // ── Parse Ed25519SignatureOffsets (14 bytes at offset 2) ──
+    //  [2..4]  signature_offset          u16 LE
+    //  [4..6]  signature_instruction_idx  u16 LE
+    //  [6..8]  public_key_offset         u16 LE
+    //  [8..10] public_key_instruction_idx u16 LE
+    // [10..12] message_data_offset       u16 LE
+    // [12..14] message_data_size         u16 LE
+    // [14..16] message_instruction_idx   u16 LE
+    let _sig_offset = u16::from_le_bytes([data[2], data[3]]) as usize;
+    let sig_ix_idx = u16::from_le_bytes([data[4], data[5]]);
+    let pk_offset = u16::from_le_bytes([data[6], data[7]]) as usize;
+    let pk_ix_idx = u16::from_le_bytes([data[8], data[9]]);
+    let msg_offset = u16::from_le_bytes([data[10], data[11]]) as usize;
+    let msg_size = u16::from_le_bytes([data[12], data[13]]) as usize;
+    let msg_ix_idx = u16::from_le_bytes([data[14], data[15]]);
+
+    // (e) All data must be embedded in this instruction (0xFFFF)
+    require!(sig_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
+    require!(pk_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
+    require!(msg_ix_idx == u16::MAX, FlappyError::InvalidEd25519Instruction);
+
+    // Bounds checks
+    require!(
+        data.len() >= pk_offset.saturating_add(32),
+        FlappyError::InvalidEd25519Instruction
+    );
+    require!(
+        data.len() >= msg_offset.saturating_add(msg_size),
+        FlappyError::InvalidEd25519Instruction
+    );
+
+    let mut pk_bytes = [0u8; 32];
+    pk_bytes.copy_from_slice(&data[pk_offset..pk_offset + 32]);
+    let message = &data[msg_offset..msg_offset + msg_size];
+
+    Ok((Pubkey::new_from_array(pk_bytes), message))
+}
+
+/// True for the `DEATH_CAUSE_*` codes this program knows about.
+fn is_valid_death_cause(cause: u8) -> bool {
+    matches!(
+        cause,
+        DEATH_CAUSE_UNKNOWN | DEATH_CAUSE_BULLET | DEATH_CAUSE_PIPE | DEATH_CAUSE_BORDER
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRICE_FEED: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    const ORACLE_PROGRAM: Pubkey = Pubkey::new_from_array([9u8; 32]);
+    const NOW: i64 = 1_700_000_000;
+
+    fn key(id: u8) -> Pubkey {
+        Pubkey::new_from_array([id; 32])
+    }
+
+    /// Pyth v2 price account bytes with the fields `load_sol_usd_price` reads.
+    fn pyth_price_account(price: i64, expo: i32, status: u32, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0u8; 3_312];
+        data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&PYTH_VERSION_2.to_le_bytes());
+        data[8..12].copy_from_slice(&PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[20..24].copy_from_slice(&expo.to_le_bytes());
+        data[96..104].copy_from_slice(&publish_time.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[224..228].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+
+    /// `PriceUpdateV2` account bytes with the fields `load_sol_usd_price`
+    /// reads.
+    fn price_update_v2(price: i64, conf: u64, expo: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = vec![0u8; PRICE_UPDATE_V2_MIN_LEN];
+        data[0..8].copy_from_slice(&PRICE_UPDATE_V2_DISCRIMINATOR);
+        data[40] = PRICE_UPDATE_V2_VERIFICATION_FULL;
+        data[73..81].copy_from_slice(&price.to_le_bytes());
+        data[81..89].copy_from_slice(&conf.to_le_bytes());
+        data[89..93].copy_from_slice(&expo.to_le_bytes());
+        data[93..101].copy_from_slice(&publish_time.to_le_bytes());
+        data
+    }
+
+    /// Config as `initialize` would create it with `authority` as the
+    /// server key; tests override the fields they exercise.
+    fn test_config(authority: Pubkey) -> VaultConfig {
+        initial_config(key(0xE1), authority, key(0xE2), 255, 254, PRICE_FEED, ORACLE_PROGRAM)
+    }
+
+    /// Token-2022 mint bytes carrying `extensions`, each left at its default
+    /// except the transfer fee (1%, capped at 1_000_000 base units).
+    fn token_2022_mint(extensions: &[ExtensionType]) -> Vec<u8> {
+        use spl_token_2022::extension::{
+            mint_close_authority::MintCloseAuthority, permanent_delegate::PermanentDelegate,
+            transfer_fee::TransferFeeConfig, transfer_hook::TransferHook,
+            BaseStateWithExtensionsMut, StateWithExtensionsMut,
+        };
+        use spl_token_2022::state::Mint as MintState;
+
+        let len = ExtensionType::try_calculate_account_len::<MintState>(extensions).unwrap();
+        let mut data = vec![0u8; len];
+        let mut state =
+            StateWithExtensionsMut::<MintState>::unpack_uninitialized(&mut data).unwrap();
+        for extension in extensions {
+            match extension {
+                ExtensionType::TransferHook => {
+                    state.init_extension::<TransferHook>(true).unwrap();
+                }
+                ExtensionType::PermanentDelegate => {
+                    state.init_extension::<PermanentDelegate>(true).unwrap();
+                }
+                ExtensionType::MintCloseAuthority => {
+                    state.init_extension::<MintCloseAuthority>(true).unwrap();
+                }
+                ExtensionType::TransferFeeConfig => {
+                    let config = state.init_extension::<TransferFeeConfig>(true).unwrap();
+                    config.newer_transfer_fee.transfer_fee_basis_points = 100.into();
+                    config.newer_transfer_fee.maximum_fee = 1_000_000.into();
+                }
+                other => panic!("no fixture for {other:?}"),
+            }
+        }
+        state.base = MintState {
+            decimals: 6,
+            is_initialized: true,
+            ..MintState::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        data
+    }
+
+    fn check_mint(mut data: Vec<u8>) -> Result<()> {
+        let (key, mut lamports) = (key(0xA1), 1_000_000u64);
+        let owner = spl_token_2022::ID;
+        let mint = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        validate_mint_extensions(&mint)
+    }
+
+    fn load(key: Pubkey, owner: Pubkey, mut data: Vec<u8>) -> Result<OraclePrice> {
+        let mut lamports = 1_000_000u64;
+        let feed = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        load_sol_usd_price(&feed, &test_config(Pubkey::default()), NOW)
+    }
+
+    #[test]
+    fn prices_usd_tier_from_local_price_account() {
+        // SOL = $150.00000000
+        let data = pyth_price_account(15_000_000_000, -8, PYTH_STATUS_TRADING, NOW - 5);
+        let price = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap();
+
+        // $5 / $150 = 0.0333… SOL, rounded up
+        assert_eq!(usd_cents_to_lamports(TIER_5_USD_CENTS, &price).unwrap(), 33_333_334);
+        // $1 / $150 = 0.00666… SOL, rounded up
+        assert_eq!(usd_cents_to_lamports(TIER_1_USD_CENTS, &price).unwrap(), 6_666_667);
+    }
+
+    #[test]
+    fn rejects_price_account_with_wrong_owner_or_key() {
+        let data = pyth_price_account(15_000_000_000, -8, PYTH_STATUS_TRADING, NOW);
+        let err = load(PRICE_FEED, Pubkey::new_unique(), data.clone()).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidPriceFeed.into());
+
+        let err = load(Pubkey::new_unique(), ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidPriceFeed.into());
+    }
+
+    #[test]
+    fn parses_auth_crate_ed25519_instruction_data() {
+        let pubkey = [4u8; 32];
+        let signature = [5u8; 64];
+        let msg = CashoutMessageV1 {
+            player: [1u8; 32],
+            session_index: 0,
+            mint: [0u8; 32],
+            max_claimable: 100,
+            nonce: 1,
+            expiry: NOW,
+            program_id: crate::id().to_bytes(),
+        }
+        .to_bytes();
+        let data = flappy_one_auth::ed25519_instruction_data(&pubkey, &signature, &msg).unwrap();
+
+        let (signer, message) = parse_ed25519_instruction(&data).unwrap();
+        assert_eq!(signer, Pubkey::new_from_array(pubkey));
+        assert_eq!(message, msg.as_slice());
+    }
+
+    #[test]
+    fn auth_hash_matches_auth_crate_golden_vector() {
+        // Same parameters as `auth_hash_matches_golden_vector` in
+        // crates/flappy-one-auth/tests/golden.rs.
+        let preimage = flappy_one_auth::auth_hash_preimage(
+            &[0x11; 32],
+            2,
+            &[0; 32],
+            1_500_000_000,
+            7,
+            1_700_000_120,
+            false,
+        );
+        let digest: String = hash(&preimage)
+            .to_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert_eq!(
+            digest,
+            "edf772e4b8e731d7beea94276df6062b4f4a9484b2318ab63effa256245da17a"
+        );
+    }
+
+    #[test]
+    fn tallies_distinct_configured_signers_against_threshold() {
+        let (a, b, c) = (key(1), key(2), key(3));
+        let mut config = test_config(a);
+        config.signers = vec![a, b, c];
+        config.threshold = 2;
+
+        assert!(tally_signers(&config, NOW, &[a, c]).is_ok());
+
+        let err = tally_signers(&config, NOW, &[a]).unwrap_err();
+        assert_eq!(err, FlappyError::SignerThresholdMismatch.into());
+        let err = tally_signers(&config, NOW, &[a, b, c]).unwrap_err();
+        assert_eq!(err, FlappyError::SignerThresholdMismatch.into());
+        let err = tally_signers(&config, NOW, &[]).unwrap_err();
+        assert_eq!(err, FlappyError::MissingEd25519Instruction.into());
+        let err = tally_signers(&config, NOW, &[b, b]).unwrap_err();
+        assert_eq!(err, FlappyError::DuplicateSigner.into());
+        let err = tally_signers(&config, NOW, &[a, key(9)]).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidAuthority.into());
+    }
+
+    #[test]
+    fn previous_authority_stands_in_only_during_overlap() {
+        let (old, new, b) = (key(1), key(2), key(3));
+        let mut config = test_config(new);
+        config.signers = vec![new, b];
+        config.threshold = 2;
+        config.previous_authority = old;
+        config.previous_authority_valid_until = NOW + 10;
+
+        assert!(tally_signers(&config, NOW, &[old, b]).is_ok());
+        // Old and new keys are the same signer, not two.
+        let err = tally_signers(&config, NOW, &[old, new]).unwrap_err();
+        assert_eq!(err, FlappyError::DuplicateSigner.into());
+        // Window closed.
+        let err = tally_signers(&config, NOW + 10, &[old, b]).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidAuthority.into());
+    }
+
+    #[test]
+    fn rotation_always_removes_outgoing_signer() {
+        let (old, new, b) = (key(1), key(2), key(3));
+
+        let mut signers = vec![old, b];
+        rotate_signer(&mut signers, old, new);
+        assert_eq!(signers, vec![new, b]);
+
+        // Incoming key already a member: the outgoing slot is dropped.
+        let mut signers = vec![old, new, b];
+        rotate_signer(&mut signers, old, new);
+        assert_eq!(signers, vec![new, b]);
+
+        // Outgoing key not a member: nothing to remove.
+        let mut signers = vec![b];
+        rotate_signer(&mut signers, old, new);
+        assert_eq!(signers, vec![b]);
+    }
+
+    #[test]
+    fn issued_at_window_tolerates_clock_skew() {
+        let started_at = NOW - 100;
+        assert!(issued_at_in_window(NOW - 50, started_at, NOW));
+        // Signer's clock ahead of the cluster clock.
+        assert!(issued_at_in_window(NOW + ISSUED_AT_SKEW_SECS, started_at, NOW));
+        assert!(!issued_at_in_window(NOW + ISSUED_AT_SKEW_SECS + 1, started_at, NOW));
+        // Cluster clock ahead of the signer's at session start.
+        assert!(issued_at_in_window(started_at - ISSUED_AT_SKEW_SECS, started_at, NOW));
+        assert!(!issued_at_in_window(started_at - ISSUED_AT_SKEW_SECS - 1, started_at, NOW));
+    }
+
+    #[test]
+    fn referral_share_rounds_down_and_needs_a_referrer() {
+        let referrer = key(7);
+        // 20% of a 133_333 lamport fee = 26_666.6 → 26_666.
+        assert_eq!(referral_share(133_333, 2_000, &referrer).unwrap(), 26_666);
+        assert_eq!(referral_share(4, 2_000, &referrer).unwrap(), 0);
+        assert_eq!(referral_share(1_000, 10_000, &referrer).unwrap(), 1_000);
+        assert_eq!(referral_share(1_000, 2_000, &Pubkey::default()).unwrap(), 0);
+    }
+
+    #[test]
+    fn referral_payout_must_leave_recipient_rent_exempt() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(0);
+        // Unfunded wallet: a share below the minimum cannot land.
+        assert!(!payout_lands(&rent, 0, 0, 133_333));
+        assert!(payout_lands(&rent, 0, 0, minimum));
+        // Funded wallet: any share lands.
+        assert!(payout_lands(&rent, minimum, 0, 1));
+        assert!(!payout_lands(&rent, u64::MAX, 0, 1));
+    }
+
+    #[test]
+    fn referrer_is_recorded_on_first_deposit_only() {
+        let (player, referrer) = (key(1), key(2));
+        let mut profile = PlayerProfile {
+            player,
+            last_nonce: 0,
+            bump: 0,
+            kills: 0,
+            deaths: 0,
+            referrer: Pubkey::default(),
+        };
+
+        // Self-referral rejected.
+        let err = record_referrer(&mut profile, Some(player), true).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidReferrer.into());
+
+        record_referrer(&mut profile, Some(referrer), true).unwrap();
+        assert_eq!(profile.referrer, referrer);
+
+        // Later deposits may repeat it or omit it, never change it.
+        record_referrer(&mut profile, Some(referrer), false).unwrap();
+        record_referrer(&mut profile, None, false).unwrap();
+        let err = record_referrer(&mut profile, Some(key(3)), false).unwrap_err();
+        assert_eq!(err, FlappyError::ReferrerAlreadySet.into());
+        assert_eq!(profile.referrer, referrer);
+
+        // No referrer on the first deposit: none can be added later.
+        let mut profile = PlayerProfile {
+            referrer: Pubkey::default(),
+            ..profile
+        };
+        let err = record_referrer(&mut profile, Some(referrer), false).unwrap_err();
+        assert_eq!(err, FlappyError::ReferrerAlreadySet.into());
+    }
+
+    #[test]
+    fn fee_split_dust_goes_to_first_recipient() {
+        let split = |id: u8, bps: u16| FeeSplit {
+            recipient: Pubkey::new_from_array([id; 32]),
+            bps,
+        };
+        let splits = [split(1, 3_333), split(2, 3_333), split(3, 3_334)];
+
+        // 1 lamport: every share rounds to zero, the dust goes to the first.
+        assert_eq!(split_fee(1, &splits).unwrap(), vec![1, 0, 0]);
+        // 100 lamports: 33 + 33 + 33 = 99, one lamport of dust.
+        assert_eq!(split_fee(100, &splits).unwrap(), vec![34, 33, 33]);
+        // Exact multiples leave no dust.
+        assert_eq!(split_fee(10_000, &splits).unwrap(), vec![3_333, 3_333, 3_334]);
+        assert_eq!(split_fee(0, &splits).unwrap(), vec![0, 0, 0]);
+
+        // Shares always add back up to the fee.
+        for fee in [7, 999, 123_456_789, u64::MAX] {
+            let total: u128 = split_fee(fee, &splits).unwrap().iter().map(|&s| s as u128).sum();
+            assert_eq!(total, fee as u128);
+        }
+    }
+
+    #[test]
+    fn mint_extensions_that_can_move_vault_funds_are_refused() {
+        let err = check_mint(token_2022_mint(&[ExtensionType::TransferHook])).unwrap_err();
+        assert_eq!(err, FlappyError::UnsupportedTransferHook.into());
+        let err = check_mint(token_2022_mint(&[ExtensionType::PermanentDelegate])).unwrap_err();
+        assert_eq!(err, FlappyError::UnsupportedPermanentDelegate.into());
+        // Not on the allowlist of balance-neutral extensions.
+        let err = check_mint(token_2022_mint(&[ExtensionType::MintCloseAuthority])).unwrap_err();
+        assert_eq!(err, FlappyError::UnsupportedMintExtension.into());
+        // One bad extension is enough.
+        let mixed = [ExtensionType::TransferFeeConfig, ExtensionType::PermanentDelegate];
+        let err = check_mint(token_2022_mint(&mixed)).unwrap_err();
+        assert_eq!(err, FlappyError::UnsupportedPermanentDelegate.into());
+    }
+
+    #[test]
+    fn transfer_fee_mints_are_accepted_and_credit_the_amount_received() {
+        use spl_token_2022::extension::transfer_fee::TransferFeeConfig;
+        use spl_token_2022::state::Mint as MintState;
+
+        let data = token_2022_mint(&[ExtensionType::TransferFeeConfig]);
+        assert!(check_mint(data.clone()).is_ok());
+        assert!(check_mint(token_2022_mint(&[])).is_ok());
+
+        // A 5_000_000 base-unit tier deposit loses the mint's 1% on the way in.
+        let state = StateWithExtensions::<MintState>::unpack(&data).unwrap();
+        let fee = state
+            .get_extension::<TransferFeeConfig>()
+            .unwrap()
+            .calculate_epoch_fee(0, 5_000_000)
+            .unwrap();
+        assert_eq!(fee, 50_000);
+        let before = 12_345;
+        let after = before + 5_000_000 - fee;
+        assert_eq!(deposit_received(before, after).unwrap(), 4_950_000);
+        assert_eq!(
+            deposit_received(after, before).unwrap_err(),
+            FlappyError::MathOverflow.into()
+        );
+    }
+
+    #[test]
+    fn fee_shares_that_cannot_land_go_to_first_recipient() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(0);
+        let funded = (minimum, 0);
+        let unfunded = (0, 0);
+        // A $1-tier fee split 50/50: each half is far below the minimum.
+        let shares = vec![66_666, 66_666];
+
+        // Funded recipients take their shares as split.
+        let routed = route_fee_shares(&rent, shares.clone(), &[funded, funded]).unwrap();
+        assert_eq!(routed, vec![66_666, 66_666]);
+        // An unfunded second recipient's share moves to the first.
+        let routed = route_fee_shares(&rent, shares.clone(), &[funded, unfunded]).unwrap();
+        assert_eq!(routed, vec![133_332, 0]);
+        // If the first cannot take it either, the fee stays in the vault.
+        let routed = route_fee_shares(&rent, shares.clone(), &[unfunded, unfunded]).unwrap();
+        assert_eq!(routed, vec![0, 0]);
+        // A share large enough to fund the account lands on its own.
+        let routed = route_fee_shares(&rent, vec![1, minimum], &[funded, unfunded]).unwrap();
+        assert_eq!(routed, vec![1, minimum]);
+    }
+
+    #[test]
+    fn profit_fee_base_ignores_returned_deposit() {
+        // Break-even and below: no profit, no fee base.
+        assert_eq!(cashout_profit(1_000, 0, 1_000), 0);
+        assert_eq!(cashout_profit(1_000, 0, 400), 0);
+        // Full cashout in profit.
+        assert_eq!(cashout_profit(1_000, 0, 1_500), 500);
+        // Partial cashouts: only the part past the deposit counts, once.
+        assert_eq!(cashout_profit(1_000, 0, 800), 0);
+        assert_eq!(cashout_profit(1_000, 800, 1_300), 300);
+        assert_eq!(cashout_profit(1_000, 1_300, 1_600), 300);
+    }
+
+    #[test]
+    fn vault_reserve_keeps_rent_min_reserve_and_active_deposits() {
+        let rent = Rent::default();
+        let rent_floor = rent.minimum_balance(0);
+        let mut config = test_config(key(0xE3));
+        config.min_reserve_lamports = 1_000;
+        config.total_active_deposits = 5_000;
+        let floor = rent_floor + 6_000;
+
+        // Paying out down to the floor is fine; one lamport more is not.
+        assert!(check_vault_reserve(&rent, floor + 700, &config, 700).is_ok());
+        let err = check_vault_reserve(&rent, floor + 700, &config, 701).unwrap_err();
+        assert_eq!(err, FlappyError::InsufficientVaultFunds.into());
+
+        // Outflow larger than the balance.
+        let err = check_vault_reserve(&rent, 10, &config, 11).unwrap_err();
+        assert_eq!(err, FlappyError::InsufficientVaultFunds.into());
+
+        // A vault funded only to rent exemption at `initialize` can pay out
+        // exactly what it holds above it.
+        let config = test_config(key(0xE3));
+        assert!(check_vault_reserve(&rent, rent_floor + 50, &config, 50).is_ok());
+        assert!(check_vault_reserve(&rent, rent_floor + 50, &config, 51).is_err());
+    }
+
+    #[test]
+    fn parses_only_the_pre_upgrade_config_layout() {
+        let (treasury, authority) = (key(1), key(2));
+        let mut data = VaultConfig::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(treasury.as_ref());
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&[254, 253]);
+        assert_eq!(data.len(), 8 + LEGACY_CONFIG_LEN);
+
+        let legacy = parse_legacy_config(&data).unwrap();
+        assert_eq!(legacy.treasury, treasury);
+        assert_eq!(legacy.authority, authority);
+        assert_eq!((legacy.vault_bump, legacy.config_bump), (254, 253));
+
+        // Re-running on a migrated account is refused.
+        let mut migrated = Vec::new();
+        initial_config(treasury, authority, key(3), 254, 253, PRICE_FEED, ORACLE_PROGRAM)
+            .try_serialize(&mut migrated)
+            .unwrap();
+        migrated.resize(8 + VaultConfig::INIT_SPACE, 0);
+        let err = parse_legacy_config(&migrated).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidLegacyConfig.into());
+
+        // Some other account type of the same size.
+        data[0] ^= 1;
+        let err = parse_legacy_config(&data).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidLegacyConfig.into());
+    }
+
+    /// Active SOL session with `deposit_amount` and `claimed` set.
+    fn sol_session(deposit_amount: u64, claimed: u64) -> Session {
+        Session {
+            player: Pubkey::default(),
+            deposit_tier: 0,
+            deposit_amount,
+            status: STATUS_ACTIVE,
+            max_claimable: 0,
+            started_at: 0,
+            nonce: 0,
+            last_auth_hash: [0u8; 32],
+            auth_expiry: 0,
+            bump: 0,
+            tier_fee_bps: None,
+            last_checkpoint_at: 0,
+            index: 0,
+            claimed,
+            mint: Pubkey::default(),
+            consumed_auth_ids: [[0u8; 32]; AUTH_RING_LEN],
+            consumed_auth_cursor: 0,
+        }
+    }
+
+    #[test]
+    fn top_up_records_only_newly_outstanding_deposit() {
+        // Nothing claimed yet: the whole top-up is outstanding.
+        let mut session = sol_session(1_000, 0);
+        assert_eq!(session.credit_top_up(500).unwrap(), 500);
+        assert_eq!(session.outstanding_deposit(), 1_500);
+
+        // Partial claims already 300 past the deposit (which released all
+        // 1_000 of it): a 200 top-up is fully covered, a further 500 only
+        // adds 400.
+        let mut config = test_config(key(1));
+        let mut session = sol_session(1_000, 1_300);
+        for lamports in [200, 500] {
+            let recorded = session.credit_top_up(lamports).unwrap();
+            config.record_deposit(recorded).unwrap();
+        }
+        assert_eq!(session.deposit_amount, 1_700);
+        assert_eq!(config.total_active_deposits, 400);
+
+        // Settling releases exactly what the top-ups recorded.
+        config.release_deposit(session.outstanding_deposit());
+        assert_eq!(config.total_active_deposits, 0);
+    }
+
+    #[test]
+    fn remembers_last_ring_len_consumed_authorizations() {
+        let mut session = sol_session(0, 0);
+
+        for id in 1..=AUTH_RING_LEN as u8 {
+            session.record_consumed_auth([id; 32]);
+        }
+        assert!((1..=AUTH_RING_LEN as u8).all(|id| session.is_auth_consumed(&[id; 32])));
+
+        // One more wraps the ring and evicts only the oldest entry.
+        session.record_consumed_auth([0xAA; 32]);
+        assert!(session.is_auth_consumed(&[0xAA; 32]));
+        assert!(!session.is_auth_consumed(&[1; 32]));
+        assert!(session.is_auth_consumed(&[2; 32]));
+    }
+
+    #[test]
+    fn prices_usd_tier_from_price_update_v2() {
+        let data = price_update_v2(15_000_000_000, 7_500_000, -8, NOW - 5);
+        let price = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap();
+        assert_eq!(usd_cents_to_lamports(TIER_5_USD_CENTS, &price).unwrap(), 33_333_334);
+
+        // Partially verified updates are rejected.
+        let mut data = price_update_v2(15_000_000_000, 7_500_000, -8, NOW - 5);
+        data[40] = 0;
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidPriceFeed.into());
+
+        let data = price_update_v2(15_000_000_000, 0, -8, NOW - DEFAULT_MAX_PRICE_AGE_SECS - 1);
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::StalePrice.into());
+    }
+
+    #[test]
+    fn rejects_price_with_wide_confidence_interval() {
+        // 2% of $150 = $3 is the widest accepted interval.
+        let data = price_update_v2(15_000_000_000, 300_000_000, -8, NOW);
+        assert!(load(PRICE_FEED, ORACLE_PROGRAM, data).is_ok());
+
+        let data = price_update_v2(15_000_000_000, 300_000_001, -8, NOW);
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::PriceTooUncertain.into());
+
+        let mut data = pyth_price_account(15_000_000_000, -8, PYTH_STATUS_TRADING, NOW);
+        data[216..224].copy_from_slice(&300_000_001u64.to_le_bytes());
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::PriceTooUncertain.into());
+    }
+
+    #[test]
+    fn rejects_stale_or_halted_price() {
+        let stale_at = NOW - DEFAULT_MAX_PRICE_AGE_SECS - 1;
+        let data = pyth_price_account(15_000_000_000, -8, PYTH_STATUS_TRADING, stale_at);
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::StalePrice.into());
+
+        let data = pyth_price_account(15_000_000_000, -8, 0, NOW);
+        let err = load(PRICE_FEED, ORACLE_PROGRAM, data).unwrap_err();
+        assert_eq!(err, FlappyError::InvalidPrice.into());
+    }
+
+    /// A player's index-0 session (1_000 lamports deposited, `status`) and
+    /// profile, each at its PDA and serialized as the program stores it.
+    fn force_close_fixture(player: Pubkey, status: u8) -> [(Pubkey, Vec<u8>); 2] {
+        let (session_key, session_bump) =
+            Pubkey::find_program_address(&[b"session", player.as_ref(), &[0]], &crate::ID);
+        let (profile_key, profile_bump) =
+            Pubkey::find_program_address(&[b"player", player.as_ref()], &crate::ID);
+        let session = Session {
+            player,
+            status,
+            bump: session_bump,
+            ..sol_session(1_000, 0)
+        };
+        let profile = PlayerProfile {
+            player,
+            last_nonce: 0,
+            bump: profile_bump,
+            kills: 0,
+            deaths: 0,
+            referrer: Pubkey::default(),
+        };
+        let (mut session_data, mut profile_data) = (Vec::new(), Vec::new());
+        session.try_serialize(&mut session_data).unwrap();
+        profile.try_serialize(&mut profile_data).unwrap();
+        [(session_key, session_data), (profile_key, profile_data)]
+    }
+
+    fn writable<'a>(key: &'a Pubkey, lamports: &'a mut u64, data: &'a mut [u8]) -> AccountInfo<'a> {
+        AccountInfo::new(key, false, true, lamports, data, &crate::ID, false, 0)
+    }
+
+    #[test]
+    fn force_close_pair_closes_each_active_session_once() {
+        let [(session_key, mut session_data), (profile_key, mut profile_data)] =
+            force_close_fixture(key(1), STATUS_ACTIVE);
+        let (mut session_lamports, mut profile_lamports) = (1u64, 1u64);
+        let session = writable(&session_key, &mut session_lamports, &mut session_data);
+        let profile = writable(&profile_key, &mut profile_lamports, &mut profile_data);
+        let mut config = test_config(Pubkey::default());
+        config.record_deposit(1_000).unwrap();
+
+        let closed = force_close_pair(&mut config, &session, &profile).unwrap().unwrap();
+        assert_eq!(closed.status, STATUS_CLOSED);
+        assert_eq!(config.total_active_deposits, 0);
+
+        // The same session listed again is already closed: skipped.
+        assert!(force_close_pair(&mut config, &session, &profile).unwrap().is_none());
+        let stored = PlayerProfile::try_deserialize(&mut &profile.data.borrow()[..]).unwrap();
+        assert_eq!(stored.deaths, 1);
+
+        // A session closed before the batch is skipped without touching
+        // its profile, even one that would not match.
+        let [(closed_key, mut closed_data), _] = force_close_fixture(key(2), STATUS_CLOSED);
+        let mut closed_lamports = 1u64;
+        let closed = writable(&closed_key, &mut closed_lamports, &mut closed_data);
+        assert!(force_close_pair(&mut config, &closed, &profile).unwrap().is_none());
+    }
+
+    #[test]
+    fn force_close_pair_rejects_accounts_off_their_pdas() {
+        let [(session_key, mut session_data), _] = force_close_fixture(key(1), STATUS_ACTIVE);
+        let [_, (other_profile_key, mut other_profile_data)] =
+            force_close_fixture(key(2), STATUS_ACTIVE);
+        let (mut session_lamports, mut profile_lamports) = (1u64, 1u64);
+        let other_profile =
+            writable(&other_profile_key, &mut profile_lamports, &mut other_profile_data);
+        let mut config = test_config(Pubkey::default());
+
+        // Session bytes at an address that is not their PDA.
+        let mut moved_data = session_data.clone();
+        let (moved_key, mut moved_lamports) = (key(0xBB), 1u64);
+        let moved = writable(&moved_key, &mut moved_lamports, &mut moved_data);
+        let err = force_close_pair(&mut config, &moved, &other_profile).err().unwrap();
+        assert_eq!(err, FlappyError::InvalidSessionAccount.into());
+
+        // Another player's profile aborts the batch; the session stays open.
+        let session = writable(&session_key, &mut session_lamports, &mut session_data);
+        let err = force_close_pair(&mut config, &session, &other_profile).err().unwrap();
+        assert_eq!(err, FlappyError::InvalidProfileAccount.into());
+        let stored = Session::try_deserialize(&mut &session.data.borrow()[..]).unwrap();
+        assert_eq!(stored.status, STATUS_ACTIVE);
+    }
 }
@@ -9,7 +9,8 @@
  *   1. Game server calls this function with an API key + player info.
  *   2. Function reads the on-chain Session PDA to get current nonce + status.
  *   3. Function validates limits and rate-limits.
- *   4. Function signs the canonical 108-byte authorization message.
+ *   4. Function signs the canonical authorization message (V1: 141 bytes,
+ *      V2: 223 bytes — see CASHOUT_MESSAGE_VERSION).
  *   5. Returns signature + parameters to the game server (→ client).
  *
  * Environment variables (set in Supabase dashboard):
@@ -17,6 +18,8 @@
  *   SOLANA_RPC_URL        — devnet RPC endpoint
  *   PROGRAM_ID            — deployed program ID (base58)
  *   API_SECRET            — shared secret for server-to-server auth
+ *   CASHOUT_MESSAGE_VERSION — "1" (default) or "2"; must be enabled on-chain
+ *   CLUSTER_GENESIS_HASH  — cluster genesis hash (base58), required for V2
  *   SUPABASE_URL          — auto-injected
  *   SUPABASE_SERVICE_ROLE_KEY — auto-injected
  */
@@ -28,19 +31,16 @@ import {
   decode as b64Decode,
   encode as b64Encode,
 } from "https://deno.land/std@0.168.0/encoding/base64.ts";
+import { buildCashoutMessage, buildCashoutMessageV2 } from "./message.ts";
 
 // ── Constants ──────────────────────────────────────────────────────────────
 
-const DOMAIN_SEPARATOR = new TextEncoder().encode("FLAPPYONE_CASHOUT_V1"); // 20 bytes
 const AUTH_EXPIRY_SECONDS = 120; // 2-minute window
-const LAMPORTS_PER_SOL = 1_000_000_000;
 
-// Tier caps: max payout per tier (generous for devnet; tighten for mainnet)
-const TIER_CAPS: Record<number, number> = {
-  1: 10 * LAMPORTS_PER_SOL, // max 10 SOL from a 1-SOL session
-  5: 50 * LAMPORTS_PER_SOL,
-  20: 200 * LAMPORTS_PER_SOL,
-};
+// Payout cap as a multiple of the session's on-chain deposit. Tiers are
+// priced in USD, so a fixed per-tier lamport cap would drift with SOL/USD
+// and ignore token deposits entirely.
+const MAX_PAYOUT_MULTIPLIER = 10n;
 
 // On-chain Session account layout offsets (after 8-byte discriminator)
 const SESSION_OFFSETS = {
@@ -54,6 +54,7 @@ const SESSION_OFFSETS = {
   last_auth_hash: 74, // 32 bytes
   auth_expiry: 106, // 8 bytes
   bump: 114, // 1 byte
+  tier_fee_bps: 115, // Option<u16>: 1-byte tag (+ 2 bytes if Some)
 } as const;
 
 const STATUS_ACTIVE = 1;
@@ -137,48 +138,15 @@ function readU8(buf: Uint8Array, offset: number): number {
   return buf[offset];
 }
 
-/** Write a u64 into a Uint8Array in little-endian. */
-function u64ToLE(value: bigint): Uint8Array {
-  const buf = new Uint8Array(8);
-  const view = new DataView(buf.buffer);
-  view.setBigUint64(0, value, true);
-  return buf;
-}
-
-/** Write an i64 into a Uint8Array in little-endian. */
-function i64ToLE(value: bigint): Uint8Array {
-  const buf = new Uint8Array(8);
-  const view = new DataView(buf.buffer);
-  view.setBigInt64(0, value, true);
-  return buf;
-}
-
 /**
- * Build the canonical 108-byte cashout authorization message.
- *
- * Layout:
- *   [ 0..20)  "FLAPPYONE_CASHOUT_V1"
- *   [20..52)  player pubkey          (32 bytes)
- *   [52..60)  max_claimable          (u64 LE)
- *   [60..68)  nonce                  (u64 LE)
- *   [68..76)  expiry                 (i64 LE)
- *   [76..108) program_id             (32 bytes)
+ * Read the session's deposit mint (all zeroes = SOL). It sits after the
+ * variable-width `tier_fee_bps` option, followed by
+ * last_checkpoint_at (8) + index (1) + claimed (8).
  */
-function buildCashoutMessage(
-  playerPubkey: Uint8Array,
-  maxClaimable: bigint,
-  nonce: bigint,
-  expiry: bigint,
-  programId: Uint8Array
-): Uint8Array {
-  const msg = new Uint8Array(108);
-  msg.set(DOMAIN_SEPARATOR, 0); // 20
-  msg.set(playerPubkey, 20); // 32
-  msg.set(u64ToLE(maxClaimable), 52); // 8
-  msg.set(u64ToLE(nonce), 60); // 8
-  msg.set(i64ToLE(expiry), 68); // 8
-  msg.set(programId, 76); // 32
-  return msg;
+function readSessionMint(buf: Uint8Array): Uint8Array {
+  const feeTag = buf[SESSION_OFFSETS.tier_fee_bps];
+  const offset = SESSION_OFFSETS.tier_fee_bps + (feeTag ? 3 : 1) + 8 + 1 + 8;
+  return buf.slice(offset, offset + 32);
 }
 
 /** Fetch a Solana account's data via JSON-RPC. */
@@ -241,6 +209,9 @@ serve(async (req: Request) => {
       player_pubkey, // base58
       max_claimable_lamports, // number (server computed)
       session_pda, // base58 (optional — can derive)
+      session_index = 0, // u8 — which of the player's session slots
+      amount_lamports, // number (optional) — exact amount to cash out (V2)
+      partial = false, // boolean — keep the session active (V2 only)
     } = body;
 
     if (!player_pubkey || max_claimable_lamports == null) {
@@ -250,18 +221,47 @@ serve(async (req: Request) => {
       );
     }
 
+    if (typeof partial !== "boolean") {
+      return new Response(
+        JSON.stringify({ error: "partial must be a boolean" }),
+        { status: 400 }
+      );
+    }
+
+    if (!Number.isInteger(session_index) || session_index < 0 || session_index > 255) {
+      return new Response(
+        JSON.stringify({ error: "session_index must be an integer in 0..=255" }),
+        { status: 400 }
+      );
+    }
+
     // ── Load env ──
     const authoritySecretB64 = Deno.env.get("AUTHORITY_SECRET_KEY");
     const rpcUrl = Deno.env.get("SOLANA_RPC_URL") || "https://api.devnet.solana.com";
     const programIdB58 = Deno.env.get("PROGRAM_ID");
-
-    if (!authoritySecretB64 || !programIdB58) {
+    const messageVersion = Deno.env.get("CASHOUT_MESSAGE_VERSION") || "1";
+    const clusterGenesisB58 = Deno.env.get("CLUSTER_GENESIS_HASH");
+
+    if (
+      !authoritySecretB64 ||
+      !programIdB58 ||
+      (messageVersion !== "1" && messageVersion !== "2") ||
+      (messageVersion === "2" && !clusterGenesisB58)
+    ) {
       return new Response(
         JSON.stringify({ error: "Server misconfigured" }),
         { status: 500 }
       );
     }
 
+    // V1 does not sign `partial`, so the program only accepts it under V2
+    if (partial && messageVersion !== "2") {
+      return new Response(
+        JSON.stringify({ error: "partial cashouts require message version 2" }),
+        { status: 400 }
+      );
+    }
+
     const authoritySecret = b64Decode(authoritySecretB64);
     const authorityKeypair = nacl.sign.keyPair.fromSecretKey(authoritySecret);
     const programIdBytes = base58Decode(programIdB58);
@@ -270,7 +270,8 @@ serve(async (req: Request) => {
     // ── Read on-chain Session PDA ──
     // If session_pda not provided, the game server should pass it.
     // It can be derived client-side: PublicKey.findProgramAddressSync(
-    //   [Buffer.from("session"), playerPubkey.toBuffer()], programId)
+    //   [Buffer.from("session"), playerPubkey.toBuffer(), Buffer.from([sessionIndex])],
+    //   programId)
     if (!session_pda) {
       return new Response(
         JSON.stringify({ error: "session_pda is required" }),
@@ -297,6 +298,8 @@ serve(async (req: Request) => {
 
     const onChainNonce = readU64LE(accountData, SESSION_OFFSETS.nonce);
     const onChainTier = readU8(accountData, SESSION_OFFSETS.deposit_tier);
+    const onChainDeposit = readU64LE(accountData, SESSION_OFFSETS.deposit_amount);
+    const onChainMint = readSessionMint(accountData);
 
     // ── Validate max_claimable ──
     const maxClaimable = BigInt(max_claimable_lamports);
@@ -309,17 +312,26 @@ serve(async (req: Request) => {
       );
     }
 
-    // GUARD: tier-based cap
-    const tierCap = BigInt(TIER_CAPS[onChainTier] || TIER_CAPS[1]);
-    if (maxClaimable > tierCap) {
+    // GUARD: deposit-relative cap
+    const payoutCap = onChainDeposit * MAX_PAYOUT_MULTIPLIER;
+    if (maxClaimable > payoutCap) {
       return new Response(
         JSON.stringify({
-          error: `max_claimable exceeds tier ${onChainTier} cap of ${tierCap} lamports`,
+          error: `max_claimable exceeds tier ${onChainTier} cap of ${payoutCap} (${MAX_PAYOUT_MULTIPLIER}x deposit)`,
         }),
         { status: 400 }
       );
     }
 
+    // V2 binds the exact amount; default to a full cashout of the ceiling
+    const amount = amount_lamports == null ? maxClaimable : BigInt(amount_lamports);
+    if (amount <= 0n || amount > maxClaimable) {
+      return new Response(
+        JSON.stringify({ error: "amount must be > 0 and <= max_claimable" }),
+        { status: 400 }
+      );
+    }
+
     // ── Rate limiting via Supabase ──
     const supabaseUrl = Deno.env.get("SUPABASE_URL")!;
     const supabaseKey = Deno.env.get("SUPABASE_SERVICE_ROLE_KEY")!;
@@ -343,15 +355,35 @@ serve(async (req: Request) => {
 
     // ── Build authorization ──
     const nonce = onChainNonce;
-    const expiry = BigInt(Math.floor(Date.now() / 1000) + AUTH_EXPIRY_SECONDS);
-
-    const message = buildCashoutMessage(
-      playerPubkeyBytes,
-      maxClaimable,
-      nonce,
-      expiry,
-      programIdBytes
-    );
+    const issuedAt = BigInt(Math.floor(Date.now() / 1000));
+    const expiry = issuedAt + BigInt(AUTH_EXPIRY_SECONDS);
+
+    const message =
+      messageVersion === "2"
+        ? buildCashoutMessageV2(
+            playerPubkeyBytes,
+            base58Decode(session_pda),
+            session_index,
+            onChainTier,
+            onChainMint,
+            amount,
+            maxClaimable,
+            nonce,
+            expiry,
+            programIdBytes,
+            base58Decode(clusterGenesisB58!),
+            issuedAt,
+            partial
+          )
+        : buildCashoutMessage(
+            playerPubkeyBytes,
+            session_index,
+            onChainMint,
+            maxClaimable,
+            nonce,
+            expiry,
+            programIdBytes
+          );
 
     // ── Sign with authority key ──
     const signature = nacl.sign.detached(message, authorityKeypair.secretKey);
@@ -384,8 +416,15 @@ serve(async (req: Request) => {
     return new Response(
       JSON.stringify({
         max_claimable: maxClaimable.toString(),
+        amount: amount.toString(),
+        message_version: Number(messageVersion),
+        session_index,
+        mint: base58Encode(onChainMint),
         nonce: nonce.toString(),
         expiry: expiry.toString(),
+        // Pass as the cashout's `issued_at` argument; V1 passes None.
+        issued_at: messageVersion === "2" ? issuedAt.toString() : null,
+        partial,
         signature: b64Encode(signature),
         message: b64Encode(message),
         authority_pubkey: base58Encode(authorityKeypair.publicKey),
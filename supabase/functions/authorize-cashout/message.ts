@@ -0,0 +1,115 @@
+/**
+ * Cashout authorization message builders for the authorize-cashout edge
+ * function. Kept apart from the handler so message_test.ts can check them
+ * against the golden vectors in crates/flappy-one-auth/tests/golden.rs.
+ */
+
+// Must match DOMAIN_SEPARATOR_V1/V2 in crates/flappy-one-auth. The original
+// 108-byte V1 layout's "FLAPPYONE_CASHOUT_V1" is retired; never reuse it.
+const DOMAIN_SEPARATOR = new TextEncoder().encode("FLAPPYONE_CASHOUT_1B"); // 20 bytes
+const DOMAIN_SEPARATOR_V2 = new TextEncoder().encode("FLAPPYONE_CASHOUT_V2"); // 20 bytes
+
+/** Write a u64 into a Uint8Array in little-endian. */
+function u64ToLE(value: bigint): Uint8Array {
+  const buf = new Uint8Array(8);
+  const view = new DataView(buf.buffer);
+  view.setBigUint64(0, value, true);
+  return buf;
+}
+
+/** Write an i64 into a Uint8Array in little-endian. */
+function i64ToLE(value: bigint): Uint8Array {
+  const buf = new Uint8Array(8);
+  const view = new DataView(buf.buffer);
+  view.setBigInt64(0, value, true);
+  return buf;
+}
+
+/**
+ * Build the canonical 141-byte cashout authorization message.
+ * Must stay byte-identical to `CashoutMessageV1` in crates/flappy-one-auth
+ * (golden vectors in crates/flappy-one-auth/tests/golden.rs).
+ *
+ * Layout:
+ *   [ 0..20)  "FLAPPYONE_CASHOUT_1B"
+ *   [20..52)  player pubkey          (32 bytes)
+ *   [52..60)  max_claimable          (u64 LE)
+ *   [60..68)  nonce                  (u64 LE)
+ *   [68..76)  expiry                 (i64 LE)
+ *   [76..108) program_id             (32 bytes)
+ *   [108]     session_index          (u8)
+ *   [109..141) mint                  (32 bytes, all zeroes = SOL)
+ */
+export function buildCashoutMessage(
+  playerPubkey: Uint8Array,
+  sessionIndex: number,
+  mint: Uint8Array,
+  maxClaimable: bigint,
+  nonce: bigint,
+  expiry: bigint,
+  programId: Uint8Array
+): Uint8Array {
+  const msg = new Uint8Array(141);
+  msg.set(DOMAIN_SEPARATOR, 0); // 20
+  msg.set(playerPubkey, 20); // 32
+  msg.set(u64ToLE(maxClaimable), 52); // 8
+  msg.set(u64ToLE(nonce), 60); // 8
+  msg.set(i64ToLE(expiry), 68); // 8
+  msg.set(programId, 76); // 32
+  msg[108] = sessionIndex; // 1
+  msg.set(mint, 109); // 32
+  return msg;
+}
+
+/**
+ * Build the canonical 223-byte V2 cashout authorization message.
+ * Must stay byte-identical to `CashoutMessageV2` in crates/flappy-one-auth.
+ *
+ * Layout:
+ *   [  0..20)  "FLAPPYONE_CASHOUT_V2"
+ *   [ 20..52)  player pubkey         (32 bytes)
+ *   [ 52..84)  session PDA           (32 bytes)
+ *   [ 84]      session_index         (u8)
+ *   [ 85]      deposit_tier          (u8)
+ *   [ 86..118) mint                  (32 bytes, all zeroes = SOL)
+ *   [118..126) amount                (u64 LE)
+ *   [126..134) max_claimable         (u64 LE)
+ *   [134..142) nonce                 (u64 LE)
+ *   [142..150) expiry                (i64 LE)
+ *   [150..182) program_id            (32 bytes)
+ *   [182..214) cluster_id            (32 bytes, genesis hash)
+ *   [214..222) issued_at             (i64 LE)
+ *   [222]      partial               (u8, 0 | 1)
+ */
+export function buildCashoutMessageV2(
+  playerPubkey: Uint8Array,
+  sessionPda: Uint8Array,
+  sessionIndex: number,
+  depositTier: number,
+  mint: Uint8Array,
+  amount: bigint,
+  maxClaimable: bigint,
+  nonce: bigint,
+  expiry: bigint,
+  programId: Uint8Array,
+  clusterId: Uint8Array,
+  issuedAt: bigint,
+  partial: boolean
+): Uint8Array {
+  const msg = new Uint8Array(223);
+  msg.set(DOMAIN_SEPARATOR_V2, 0); // 20
+  msg.set(playerPubkey, 20); // 32
+  msg.set(sessionPda, 52); // 32
+  msg[84] = sessionIndex; // 1
+  msg[85] = depositTier; // 1
+  msg.set(mint, 86); // 32
+  msg.set(u64ToLE(amount), 118); // 8
+  msg.set(u64ToLE(maxClaimable), 126); // 8
+  msg.set(u64ToLE(nonce), 134); // 8
+  msg.set(i64ToLE(expiry), 142); // 8
+  msg.set(programId, 150); // 32
+  msg.set(clusterId, 182); // 32
+  msg.set(i64ToLE(issuedAt), 214); // 8
+  msg[222] = partial ? 1 : 0; // 1
+  return msg;
+}
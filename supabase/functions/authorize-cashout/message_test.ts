@@ -0,0 +1,85 @@
+/**
+ * Checks the edge function's message builders against the golden vectors in
+ * crates/flappy-one-auth/tests/golden.rs — the bytes the program verifies.
+ * Update both files together; a mismatch means the layouts have drifted.
+ *
+ * Run: deno test supabase/functions/authorize-cashout/message_test.ts
+ */
+
+import { assertEquals } from "https://deno.land/std@0.168.0/testing/asserts.ts";
+import { buildCashoutMessage, buildCashoutMessageV2 } from "./message.ts";
+
+/** `8b4U8WX2SNJ1p53m2w6GcMjCooo7KTGdWZiFBmcZ4MwK` (devnet program id). */
+const PROGRAM_ID = hexToBytes(
+  "70bb7a1bffffba4a4676d59eb9cbd12be58eeb92cfb11378d1c1f1307999b476"
+);
+
+const V1_HEX = [
+  "464c415050594f4e455f434153484f55545f3142", // "FLAPPYONE_CASHOUT_1B"
+  "1111111111111111111111111111111111111111111111111111111111111111", // player
+  "002f685900000000", // max_claimable = 1_500_000_000
+  "0700000000000000", // nonce = 7
+  "78f1536500000000", // expiry = 1_700_000_120
+  "70bb7a1bffffba4a4676d59eb9cbd12be58eeb92cfb11378d1c1f1307999b476", // program_id
+  "02", // session_index
+  "0000000000000000000000000000000000000000000000000000000000000000", // mint (SOL)
+].join("");
+
+const V2_HEX = [
+  "464c415050594f4e455f434153484f55545f5632", // "FLAPPYONE_CASHOUT_V2"
+  "1111111111111111111111111111111111111111111111111111111111111111", // player
+  "3333333333333333333333333333333333333333333333333333333333333333", // session PDA
+  "02", // session_index
+  "05", // deposit_tier
+  "4444444444444444444444444444444444444444444444444444444444444444", // mint
+  "801a060000000000", // amount = 400_000
+  "40420f0000000000", // max_claimable = 1_000_000
+  "0700000000000000", // nonce = 7
+  "78f1536500000000", // expiry = 1_700_000_120
+  "70bb7a1bffffba4a4676d59eb9cbd12be58eeb92cfb11378d1c1f1307999b476", // program_id
+  "5555555555555555555555555555555555555555555555555555555555555555", // cluster_id
+  "00f1536500000000", // issued_at = 1_700_000_000
+  "01", // partial
+].join("");
+
+function hexToBytes(hex: string): Uint8Array {
+  return Uint8Array.from(hex.match(/../g)!, (b) => parseInt(b, 16));
+}
+
+function toHex(bytes: Uint8Array): string {
+  return Array.from(bytes, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+Deno.test("V1 message matches the auth crate's golden vector", () => {
+  const msg = buildCashoutMessage(
+    new Uint8Array(32).fill(0x11),
+    2,
+    new Uint8Array(32),
+    1_500_000_000n,
+    7n,
+    1_700_000_120n,
+    PROGRAM_ID
+  );
+  assertEquals(msg.length, 141);
+  assertEquals(toHex(msg), V1_HEX);
+});
+
+Deno.test("V2 message matches the auth crate's golden vector", () => {
+  const msg = buildCashoutMessageV2(
+    new Uint8Array(32).fill(0x11),
+    new Uint8Array(32).fill(0x33),
+    2,
+    5,
+    new Uint8Array(32).fill(0x44),
+    400_000n,
+    1_000_000n,
+    7n,
+    1_700_000_120n,
+    PROGRAM_ID,
+    new Uint8Array(32).fill(0x55),
+    1_700_000_000n,
+    true
+  );
+  assertEquals(msg.length, 223);
+  assertEquals(toHex(msg), V2_HEX);
+});